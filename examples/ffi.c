@@ -0,0 +1,70 @@
+/*
+ * Plays a short game through the C interface and checks the rules agree with
+ * the Rust side. With a database path it also prints the best move from the
+ * empty board.
+ *
+ *     cc examples/ffi.c -Iinclude -Ltarget/release -lconnect3 -o ffi_example
+ *     LD_LIBRARY_PATH=target/release ./ffi_example [sorted_output.bin]
+ */
+
+#include <stdio.h>
+
+#include "connect3.h"
+
+#define CHECK(condition)                                            \
+    do {                                                            \
+        if (!(condition)) {                                         \
+            fprintf(stderr, "%s:%d: %s\n", __FILE__, __LINE__, #condition); \
+            return 1;                                               \
+        }                                                           \
+    } while (0)
+
+int main(int argc, char **argv) {
+    uint64_t next[9];
+    int8_t cells[27];
+    int moves[] = {1, 2, 4, 5, 7};
+    uint64_t state = 0;
+
+    CHECK(connect3_next_numbers(0, next, 9) == 9);
+    CHECK(connect3_player_to_move(0) == 1);
+    CHECK(connect3_play(0, 10) == -1);
+
+    /* Player 1 fills the first column of the bottom layer */
+    for (size_t i = 0; i < sizeof moves / sizeof moves[0]; i++) {
+        CHECK(!connect3_is_over(state));
+        int64_t played = connect3_play(state, moves[i]);
+        CHECK(played >= 0);
+        state = (uint64_t)played;
+    }
+    printf("state %llu\n", (unsigned long long)state);
+    CHECK(connect3_is_over(state));
+    CHECK(!connect3_is_full(state));
+    CHECK(connect3_next_numbers(state, next, 9) == 0);
+    CHECK(connect3_play(state, 9) == -1);
+
+    CHECK(connect3_number_to_board(state, cells) == 2);
+    CHECK(cells[0] == 1 && cells[9] == 1 && cells[18] == 1);
+    CHECK(cells[3] == 2 && cells[12] == 2);
+    CHECK(connect3_board_to_number(cells) == (int64_t)state);
+    cells[1] = 3;
+    CHECK(connect3_board_to_number(cells) == -1);
+
+    /* Three pieces on one peg fill it */
+    state = 0;
+    for (int i = 0; i < 3; i++) {
+        state = (uint64_t)connect3_play(state, 5);
+    }
+    CHECK(connect3_play(state, 5) == -1);
+    CHECK(connect3_next_numbers(state, next, 9) == 8);
+
+    if (argc > 1) {
+        int8_t peg, winner;
+        connect3_database *database = connect3_database_open(argv[1]);
+        CHECK(database != NULL);
+        CHECK(connect3_best_move(database, 0, &peg, &winner) == 1);
+        printf("best %d winner %d\n", peg, winner);
+        connect3_database_close(database);
+    }
+    CHECK(connect3_database_open("/nonexistent/sorted_output.bin") == NULL);
+    return 0;
+}
@@ -0,0 +1,117 @@
+/*
+ * C interface to the 3x3x3 Connect 3 rules and solved database, exported by
+ * the connect3 cdylib (libconnect3.so, libconnect3.dylib or connect3.dll).
+ *
+ * Generated from src/ffi.rs by cbindgen with cbindgen.toml; do not edit.
+ * Regenerate with CONNECT3_WRITE_HEADER=1 cargo test --test ffi.
+ *
+ * States are the numbers used by the database, the sum of cell * 3^(x + 3y + 9z).
+ * Cells are passed as 27 bytes indexed x * 9 + y * 3 + z, with x going Down,
+ * y going Right and z up the peg, holding 0 for empty or the player's number.
+ * Pegs are numbered 1 to 9 along the rows.
+ */
+
+#ifndef CONNECT3_H
+#define CONNECT3_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+/**
+ * Read only handle on a sorted output file. Lookups go straight to disk so the
+ * 5 GB table never has to be loaded.
+ */
+typedef struct connect3_database connect3_database;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Whether a line of three has been made in `state`, 1 or 0.
+ */
+int connect3_is_over(uint64_t state);
+
+/**
+ * Whether every cell of `state` is taken, 1 or 0.
+ */
+int connect3_is_full(uint64_t state);
+
+/**
+ * The state after the side to move plays `peg`, or -1 if the peg is full, the
+ * game is over or either argument is out of range.
+ */
+int64_t connect3_play(uint64_t state, int peg);
+
+/**
+ * Writes the cells of `state` to `cells` and returns the player to move, or -1
+ * if the state is out of range.
+ *
+ * # Safety
+ *
+ * `cells` must point to 27 writable bytes.
+ */
+int connect3_number_to_board(uint64_t state, int8_t *cells);
+
+/**
+ * The state number of 27 cells, or -1 if a cell is not 0, 1 or 2.
+ *
+ * # Safety
+ *
+ * `cells` must point to 27 readable bytes.
+ */
+int64_t connect3_board_to_number(const int8_t *cells);
+
+/**
+ * Writes up to `capacity` of the states the side to move can reach from
+ * `state` to `next` and returns how many there are, at most 9. Nothing is
+ * generated once the game is over.
+ *
+ * # Safety
+ *
+ * `next` must point to `capacity` writable numbers, or be null when
+ * `capacity` is 0.
+ */
+size_t connect3_next_numbers(uint64_t state, uint64_t *next, size_t capacity);
+
+/**
+ * Opens a solved database, or returns null if it cannot be read. Close it with
+ * `connect3_database_close`.
+ *
+ * # Safety
+ *
+ * `path` must be a null terminated string.
+ */
+connect3_database *connect3_database_open(const char *path);
+
+/**
+ * # Safety
+ *
+ * `database` must come from `connect3_database_open` and not be used again.
+ * Null is ignored.
+ */
+void connect3_database_close(connect3_database *database);
+
+/**
+ * Looks up the stored move and the winner under perfect play (0 for a draw)
+ * for `state`. `peg` is 0 for finished games. Returns 1 when the state is in
+ * the database, 0 when it is not and -1 if the file cannot be read. The handle
+ * may be shared between threads.
+ *
+ * # Safety
+ *
+ * `database` must be an open handle, and `peg` and `winner` must each be null
+ * or point to a writable byte.
+ */
+int connect3_best_move(const connect3_database *database, uint64_t state, int8_t *peg, int8_t *winner);
+
+/**
+ * The player to move in `state`, or -1 if it is out of range.
+ */
+int connect3_player_to_move(uint64_t state);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* CONNECT3_H */
@@ -0,0 +1,77 @@
+"""Builds the connect3 Python module with cargo and packs it into a wheel.
+
+Only the standard library is used, so no build backend has to be downloaded:
+
+    python3 python/build_wheel.py [--offline] [--out-dir dist]
+    pip install --no-index dist/connect3-*.whl
+"""
+
+import argparse
+import base64
+import hashlib
+import pathlib
+import re
+import subprocess
+import sys
+import sysconfig
+import zipfile
+
+ROOT = pathlib.Path(__file__).resolve().parent.parent
+
+
+def built_library():
+    release = ROOT / "target" / "release"
+    for name in ("libconnect3.so", "libconnect3.dylib", "connect3.dll"):
+        if (release / name).exists():
+            return release / name
+    sys.exit("cargo did not produce the connect3 library in " + str(release))
+
+
+def record_line(name, data):
+    digest = base64.urlsafe_b64encode(hashlib.sha256(data).digest()).rstrip(b"=")
+    return "{},sha256={},{}".format(name, digest.decode(), len(data))
+
+
+def main():
+    parser = argparse.ArgumentParser(description=__doc__.splitlines()[0])
+    parser.add_argument("--offline", action="store_true", help="pass --offline to cargo")
+    parser.add_argument("--out-dir", default=str(ROOT / "dist"))
+    args = parser.parse_args()
+
+    command = ["cargo", "build", "--release", "--lib", "--features", "python"]
+    if args.offline:
+        command.append("--offline")
+    subprocess.run(command, cwd=ROOT, check=True)
+
+    version = re.search(r'^version = "(.+)"', (ROOT / "Cargo.toml").read_text(), re.M).group(1)
+    python_tag = "cp{}{}".format(*sys.version_info[:2])
+    platform_tag = re.sub(r"[-.]", "_", sysconfig.get_platform())
+    tag = "{}-{}-{}".format(python_tag, python_tag, platform_tag)
+    dist_info = "connect3-{}.dist-info".format(version)
+
+    files = {
+        "connect3" + sysconfig.get_config_var("EXT_SUFFIX"): built_library().read_bytes(),
+        dist_info + "/METADATA": (
+            "Metadata-Version: 2.1\nName: connect3\nVersion: {}\n"
+            "Summary: 3x3x3 Connect 3 rules and solved database\n".format(version)
+        ).encode(),
+        dist_info + "/WHEEL": (
+            "Wheel-Version: 1.0\nGenerator: build_wheel.py\nRoot-Is-Purelib: false\n"
+            "Tag: {}\n".format(tag)
+        ).encode(),
+    }
+    records = [record_line(name, data) for name, data in files.items()]
+    records.append(dist_info + "/RECORD,,")
+    files[dist_info + "/RECORD"] = ("\n".join(records) + "\n").encode()
+
+    out_dir = pathlib.Path(args.out_dir)
+    out_dir.mkdir(parents=True, exist_ok=True)
+    wheel = out_dir / "connect3-{}-{}.whl".format(version, tag)
+    with zipfile.ZipFile(wheel, "w", zipfile.ZIP_DEFLATED) as archive:
+        for name, data in files.items():
+            archive.writestr(name, data)
+    print(wheel)
+
+
+if __name__ == "__main__":
+    main()
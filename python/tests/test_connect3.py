@@ -0,0 +1,106 @@
+"""Tests for the connect3 module. Install the wheel from build_wheel.py, then run
+
+    python3 -m unittest discover -s python/tests
+"""
+
+import json
+import os
+import struct
+import tempfile
+import unittest
+
+import connect3
+
+
+def write_database(path, records):
+    """Writes (state, stored move, winner) records in the sorted output format."""
+    with open(path, "wb") as file:
+        for state, stored_move, winner in sorted(records):
+            file.write(struct.pack("<Qbb", state, stored_move, winner))
+
+
+class GameTest(unittest.TestCase):
+    def test_empty_board(self):
+        game = connect3.Game()
+        self.assertEqual(game.state, 0)
+        self.assertEqual(game.player, 1)
+        self.assertEqual(game.legal_moves(), list(range(1, 10)))
+        self.assertEqual(len(game.next_states()), 9)
+        self.assertIsNone(game.winner())
+
+    def test_play_and_encoding(self):
+        game = connect3.Game.from_moves([5, 1])
+        self.assertEqual(game.player, 1)
+        self.assertEqual(game.board[1, 1, 0], 1)
+        self.assertEqual(game.board[0, 0, 0], 2)
+        self.assertEqual(connect3.Game.from_state(game.state), game)
+        self.assertEqual(connect3.number_to_board(game.state), game)
+        self.assertEqual(connect3.board_to_number(game.board), game.state)
+        self.assertEqual(connect3.Board(game.board.cells), game.board)
+        self.assertEqual(connect3.get_all_next_numbers(game.state), game.next_states())
+
+    def test_win(self):
+        game = connect3.Game.from_moves([1, 2, 4, 5, 7])
+        self.assertTrue(game.is_over())
+        self.assertTrue(connect3.is_over(game.state))
+        self.assertEqual(game.winner(), 1)
+        self.assertEqual(game.legal_moves(), [])
+        with self.assertRaises(ValueError):
+            game.play(9)
+
+    def test_full_peg(self):
+        game = connect3.Game.from_moves([5, 5, 5])
+        self.assertNotIn(5, game.legal_moves())
+        with self.assertRaises(ValueError):
+            game.play(5)
+
+    def test_moves_between_states(self):
+        before = connect3.Game.from_moves([5])
+        after = before.play(1)
+        stored = connect3.get_move_between_board(before.state, after.state)
+        self.assertEqual(connect3.stored_move_to_peg(stored), 1)
+
+    def test_bad_input(self):
+        with self.assertRaises(ValueError):
+            connect3.Game.from_state(3**27)
+        with self.assertRaises(ValueError):
+            connect3.Board([[[0] * 3] * 3] * 2)
+        with self.assertRaises(ValueError):
+            connect3.Game(player=3)
+
+
+class DatabaseTest(unittest.TestCase):
+    def setUp(self):
+        self.directory = tempfile.TemporaryDirectory()
+        self.path = os.path.join(self.directory.name, "sorted_output.bin")
+        # The empty board with peg 5 stored, and the position after it
+        self.after = connect3.Game.from_moves([5]).state
+        write_database(self.path, [(0, 4, 1), (self.after, 0, 1)])
+
+    def tearDown(self):
+        self.directory.cleanup()
+
+    def test_lookup(self):
+        database = connect3.Database(self.path)
+        self.assertEqual(len(database), 2)
+        record = database.lookup(0)
+        self.assertEqual((record.state, record.stored_move, record.winner), (0, 4, 1))
+        self.assertEqual(record.peg, 5)
+        self.assertEqual(database.best_move(0), (5, 1))
+        self.assertIsNone(database.lookup(1))
+        self.assertEqual([record.state for record in database.records()], [0, self.after])
+
+    def test_stats(self):
+        stats = connect3.Database(self.path).stats()
+        self.assertEqual(stats.positions, 2)
+        self.assertEqual(stats.depths[0].positions, 1)
+        self.assertEqual(stats.depths[1].to_move_losses, 1)
+        self.assertEqual(json.loads(stats.to_json())["positions"], 2)
+
+    def test_missing_file(self):
+        with self.assertRaises(OSError):
+            connect3.Database(os.path.join(self.directory.name, "missing.bin"))
+
+
+if __name__ == "__main__":
+    unittest.main()
@@ -0,0 +1,189 @@
+use std::path::Path;
+
+use crate::play::{self, Engine};
+use crate::record::GameRecord;
+use crate::variant::Variant;
+use crate::{is_full, is_over, notation, number_to_board, Game};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Verdict {
+    /// Keeps the game-theoretic value
+    Good,
+    /// Turns a win into a draw
+    Inaccuracy,
+    /// Turns a win or a draw into a loss
+    Blunder,
+}
+
+#[derive(Default)]
+struct Summary {
+    moves: usize,
+    inaccuracies: usize,
+    blunders: usize,
+}
+
+/// 1 for a win, 0 for a draw and -1 for a loss, from `player`'s point of view.
+fn value(winner: i8, player: i8) -> i8 {
+    match winner {
+        0 => 0,
+        w if w == player => 1,
+        _ => -1,
+    }
+}
+
+fn describe(value: i8) -> &'static str {
+    match value {
+        1 => "win",
+        0 => "draw",
+        _ => "loss",
+    }
+}
+
+/// The winner under perfect play, taking finished games from the board.
+fn winner_of(engine: &mut Engine, g: &Game, mover: i8) -> Result<i8, String> {
+    if is_over(g) {
+        Ok(mover)
+    } else if is_full(g) {
+        Ok(0)
+    } else {
+        engine.evaluate(g)
+    }
+}
+
+/// Marks every move of the record against the engine's values and returns the
+/// tally for each player.
+fn annotate_record(
+    engine: &mut Engine,
+    variant: &Variant,
+    record: &mut GameRecord,
+) -> Result<[Summary; 2], String> {
+    let pegs = record.pegs();
+    let mut summaries = [Summary::default(), Summary::default()];
+    for ply in 0..pegs.len() {
+        let before = number_to_board(notation::play_moves(variant, &pegs[..ply])?.state_number());
+        let after =
+            number_to_board(notation::play_moves(variant, &pegs[..ply + 1])?.state_number());
+        let mover = before.player;
+        let was = value(engine.evaluate(&before)?, mover);
+        let now = value(winner_of(engine, &after, mover)?, mover);
+        let verdict = match (was, now) {
+            (1, 0) => Verdict::Inaccuracy,
+            (_, -1) if was > -1 => Verdict::Blunder,
+            _ => Verdict::Good,
+        };
+
+        let summary = &mut summaries[(mover - 1) as usize];
+        summary.moves += 1;
+        // Good moves keep whatever marks and comments the record already had
+        let mark = match verdict {
+            Verdict::Good => continue,
+            Verdict::Inaccuracy => {
+                summary.inaccuracies += 1;
+                "?"
+            }
+            Verdict::Blunder => {
+                summary.blunders += 1;
+                "??"
+            }
+        };
+        let best = engine.choose(variant, &before)?;
+        let record_move = &mut record.moves[ply];
+        record_move.mark = mark.to_string();
+        record_move.comment = Some(format!(
+            "{} to {}, {} keeps the {}",
+            describe(was),
+            describe(now),
+            notation::format_peg(best),
+            describe(was)
+        ));
+    }
+    Ok(summaries)
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let variant = Variant::new(3, 3).unwrap();
+    let input = crate::positional(args, 0).ok_or("Give a game record or a move sequence")?;
+    let mut record = if Path::new(input).is_file() {
+        GameRecord::read(input)?
+    } else {
+        // A bare move list gets the headers that play and tournament write
+        GameRecord::new(&GameRecord::parse(input)?.pegs())
+    };
+    if !record.variant()?.is_standard() {
+        return Err("Only 3x3x3 connect 3 games can be annotated".to_string());
+    }
+    let mut engine = play::open_engine(args, &variant)?;
+    let summaries = annotate_record(&mut engine, &variant, &mut record)?;
+
+    match crate::flag_value(args, "--output") {
+        Some(path) => {
+            record
+                .write(path)
+                .map_err(|error| format!("Could not write {}: {}", path, error))?;
+            println!("Annotated record written to {}", path);
+        }
+        None => print!("{}", record),
+    }
+    println!();
+    for (index, summary) in summaries.iter().enumerate() {
+        let good = summary.moves - summary.inaccuracies - summary.blunders;
+        let accuracy = if summary.moves > 0 {
+            100.0 * good as f64 / summary.moves as f64
+        } else {
+            100.0
+        };
+        println!(
+            "Player {}: {} moves, {} good, {} inaccuracies, {} blunders, accuracy {:.0}%",
+            index + 1,
+            summary.moves,
+            good,
+            summary.inaccuracies,
+            summary.blunders,
+            accuracy
+        );
+    }
+    Ok(())
+}
+
+/// `annotate FILE|MOVES [--output FILE] [--engine database|search] [--database PATH]`
+pub fn annotate(args: &[String]) {
+    if let Err(error) = run(args) {
+        println!("{}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::negamax::Searcher;
+
+    fn annotate(text: &str) -> (GameRecord, [Summary; 2]) {
+        let variant = Variant::new(3, 3).unwrap();
+        let mut engine = Engine::Search(Searcher::new(&variant, 16));
+        let mut record = GameRecord::parse(text).unwrap();
+        let summaries = annotate_record(&mut engine, &variant, &mut record).unwrap();
+        (record, summaries)
+    }
+
+    #[test]
+    fn a_blunder_is_marked_with_the_move_that_keeps_the_win() {
+        // Player 1 wins from the start, and 9 in reply to 1 throws it away
+        let (record, summaries) = annotate("5 1 9");
+        assert_eq!(record.moves[2].mark, "??");
+        assert_eq!(
+            record.moves[2].comment.as_deref(),
+            Some("win to loss, 5 keeps the win")
+        );
+        assert_eq!(summaries[0].blunders, 1);
+        assert!(record.moves[..2].iter().all(|m| m.mark.is_empty()));
+    }
+
+    #[test]
+    fn comments_on_good_moves_survive() {
+        let (record, summaries) = annotate("1. 5! {the centre} 1 2. 9 *");
+        assert_eq!(record.moves[0].mark, "!");
+        assert_eq!(record.moves[0].comment.as_deref(), Some("the centre"));
+        assert_eq!(summaries[0].moves, 2);
+        assert_eq!(record.moves[2].mark, "??");
+    }
+}
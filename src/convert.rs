@@ -0,0 +1,424 @@
+use std::collections::HashSet;
+use std::io::Read;
+
+use crate::notation;
+use crate::render::{self, Highlights, Layout, Style};
+use crate::variant::{Position, Variant};
+use crate::{game_to_number, game_to_str, make_new_board, switch_player, Board, Game};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Format {
+    State,
+    Moves,
+    Grid,
+    Stacks,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Format, String> {
+        match name {
+            "state" => Ok(Format::State),
+            "moves" => Ok(Format::Moves),
+            "grid" => Ok(Format::Grid),
+            "stacks" => Ok(Format::Stacks),
+            other => Err(format!(
+                "Unknown format {}, use state, moves, grid or stacks",
+                other
+            )),
+        }
+    }
+
+    /// Grids span several lines, start with `grid` or hold 27 cells, stacks hold
+    /// slashes, move sequences have spaces, commas or squares, and anything else is
+    /// a state number.
+    fn detect(text: &str) -> Format {
+        let text = text.trim();
+        let cells: Vec<char> = text.chars().filter(|c| !c.is_whitespace()).collect();
+        if text.contains('\n')
+            || text.starts_with(GRID_PREFIX)
+            || cells.len() == 27 && cells.iter().all(|&c| grid_cell(c).is_some())
+        {
+            Format::Grid
+        } else if text.contains('/') {
+            Format::Stacks
+        } else if notation::is_move_sequence(text) {
+            Format::Moves
+        } else {
+            Format::State
+        }
+    }
+}
+
+/// The side to move follows from the piece count, as in `number_to_board`.
+fn game_from_board(board: Board) -> Game {
+    let pieces = board
+        .data
+        .iter()
+        .flatten()
+        .flatten()
+        .filter(|&&v| v != 0)
+        .count();
+    Game {
+        board,
+        player: (pieces % 2) as i8 + 1,
+    }
+}
+
+/// Marks the start of a grid written in digits.
+const GRID_PREFIX: &str = "grid";
+
+fn grid_cell(c: char) -> Option<i8> {
+    match c {
+        '0' | '.' | '-' => Some(0),
+        '1' | 'x' | 'X' => Some(1),
+        '2' | 'o' | 'O' => Some(2),
+        _ => None,
+    }
+}
+
+/// Whether a line starts a grid, for input that may hold a grid, a move sequence
+/// or a state number. Rows of digits look like moves or state numbers, so only a
+/// `grid` line or a row written with ., X or O counts.
+pub fn starts_grid(line: &str) -> bool {
+    let line = line.trim();
+    let cells: Vec<char> = line.chars().filter(|c| !c.is_whitespace()).collect();
+    line.starts_with(GRID_PREFIX)
+        || cells.len() == 3
+            && cells.iter().all(|&c| grid_cell(c).is_some())
+            && cells.iter().any(|c| !c.is_ascii_digit())
+}
+
+/// Reads the grid `game_to_str` prints: three layers from the bottom up, each
+/// three rows with Down going down the rows and Right along them, layers split by
+/// blank lines. Cells are 0, 1 and 2 or ., X and O, with or without spaces, and
+/// all 27 cells may also be given on one line. A `grid` line may come first. The
+/// board must be reachable in a game.
+pub fn parse_grid(text: &str) -> Result<Game, String> {
+    // Each block is the rows between blank lines, with the line each started on
+    let mut blocks: Vec<Vec<(usize, Vec<i8>)>> = vec![Vec::new()];
+    let mut seen_cells = false;
+    for (index, line) in text.lines().enumerate() {
+        let line_number = index + 1;
+        // An optional `grid` before the first row
+        let line = match line.trim_start().strip_prefix(GRID_PREFIX) {
+            Some(rest) if !seen_cells => rest,
+            _ => line,
+        };
+        if line.trim().is_empty() {
+            if !blocks.last().unwrap().is_empty() {
+                blocks.push(Vec::new());
+            }
+            continue;
+        }
+        let cells: Vec<i8> = line
+            .chars()
+            .filter(|c| !c.is_whitespace())
+            .map(|c| {
+                grid_cell(c).ok_or(format!(
+                    "Line {}: unexpected {:?}, cells are 0, 1, 2 or ., X, O",
+                    line_number, c
+                ))
+            })
+            .collect::<Result<_, _>>()?;
+        if cells.len() != 3 && cells.len() != 27 {
+            return Err(format!(
+                "Line {}: a row has 3 cells, found {}",
+                line_number,
+                cells.len()
+            ));
+        }
+        seen_cells = true;
+        let block = blocks.last_mut().unwrap();
+        for row in cells.chunks(3) {
+            block.push((line_number, row.to_vec()));
+        }
+    }
+    if blocks.last().unwrap().is_empty() {
+        blocks.pop();
+    }
+    if blocks.len() > 1 {
+        for (layer, block) in blocks.iter().enumerate() {
+            if block.len() != 3 {
+                return Err(format!(
+                    "Layer {} starting on line {} has {} rows, layers have 3",
+                    layer + 1,
+                    block[0].0,
+                    block.len()
+                ));
+            }
+        }
+    }
+    let rows: Vec<&Vec<i8>> = blocks.iter().flatten().map(|(_, row)| row).collect();
+    if rows.len() != 9 {
+        return Err(format!(
+            "A grid has 3 layers of 3 rows, found {} rows",
+            rows.len()
+        ));
+    }
+    let mut board = make_new_board();
+    for (index, row) in rows.iter().enumerate() {
+        for (y, &value) in row.iter().enumerate() {
+            board.data[index % 3][y][index / 3] = value;
+        }
+    }
+    let g = game_from_board(board);
+    moves_to(&g)?;
+    Ok(g)
+}
+
+/// Nine pegs in order separated by slashes, each listing its pieces from the
+/// bottom up, with - for an empty peg: "-/-/-/-/12/-/-/-/1".
+pub fn format_stacks(g: &Game) -> String {
+    let stacks: Vec<String> = (0..9)
+        .map(|peg| {
+            let stack: String = (0..3)
+                .map(|z| g.board.data[peg / 3][peg % 3][z])
+                .take_while(|&v| v != 0)
+                .map(|v| v.to_string())
+                .collect();
+            if stack.is_empty() {
+                "-".to_string()
+            } else {
+                stack
+            }
+        })
+        .collect();
+    stacks.join("/")
+}
+
+pub fn parse_stacks(text: &str) -> Result<Game, String> {
+    let stacks: Vec<&str> = text.trim().split('/').collect();
+    if stacks.len() != 9 {
+        return Err(format!("Stacks need 9 pegs, found {}", stacks.len()));
+    }
+    let mut board = make_new_board();
+    for (peg, stack) in stacks.iter().enumerate() {
+        let stack = if *stack == "-" { "" } else { stack };
+        if stack.len() > 3 {
+            return Err(format!("Peg {} holds more than 3 pieces", peg + 1));
+        }
+        for (z, c) in stack.chars().enumerate() {
+            board.data[peg / 3][peg % 3][z] = match c {
+                '1' => 1,
+                '2' => 2,
+                _ => return Err(format!("Unexpected {:?} on peg {}", c, peg + 1)),
+            };
+        }
+    }
+    Ok(game_from_board(board))
+}
+
+/// Removes the top piece of `peg` if the player who moved last owns it.
+fn take_back(variant: &Variant, position: &Position, peg: usize) -> Option<Position> {
+    let height = position.heights[peg] as usize;
+    if height == 0 {
+        return None;
+    }
+    let last = if position.player == 1 { 2 } else { 1 };
+    let bit = 1 << variant.cell(peg, height - 1);
+    if position.pieces[(last - 1) as usize] & bit == 0 {
+        return None;
+    }
+    let mut before = *position;
+    before.pieces[(last - 1) as usize] &= !bit;
+    before.heights[peg] -= 1;
+    before.moves -= 1;
+    before.player = last;
+    Some(before)
+}
+
+/// Takes pieces back one at a time without passing through a finished game, so
+/// the moves found are a legal game in reverse.
+fn unplay(
+    variant: &Variant,
+    position: &Position,
+    dead_ends: &mut HashSet<u64>,
+    moves: &mut Vec<usize>,
+) -> bool {
+    if position.moves == 0 {
+        return true;
+    }
+    if dead_ends.contains(&position.state_number()) {
+        return false;
+    }
+    for peg in 0..variant.pegs() {
+        if let Some(before) = take_back(variant, position, peg) {
+            if variant.winner(&before).is_none() && unplay(variant, &before, dead_ends, moves) {
+                moves.push(peg);
+                return true;
+            }
+        }
+    }
+    dead_ends.insert(position.state_number());
+    false
+}
+
+/// Checks that a board can come up in a game and returns moves that reach it.
+pub fn moves_to(g: &Game) -> Result<Vec<usize>, String> {
+    for peg in 0..9 {
+        for z in 1..3 {
+            let column = g.board.data[peg / 3][peg % 3];
+            if column[z] != 0 && column[z - 1] == 0 {
+                return Err(format!(
+                    "Peg {} has a piece floating at height {}",
+                    notation::format_peg(peg),
+                    z + 1
+                ));
+            }
+        }
+    }
+    let count = |player: i8| {
+        g.board
+            .data
+            .iter()
+            .flatten()
+            .flatten()
+            .filter(|&&v| v == player)
+            .count()
+    };
+    let (ones, twos) = (count(1), count(2));
+    if ones != twos && ones != twos + 1 {
+        return Err(format!(
+            "Player 1 has {} pieces and player 2 has {}, player 1 moves first",
+            ones, twos
+        ));
+    }
+
+    let variant = Variant::new(3, 3).unwrap();
+    let position = Position::from_game(&game_from_board(g.board.clone()));
+    let has_line = |player: i8| variant.has_line(position.pieces[(player - 1) as usize]);
+    if has_line(1) && has_line(2) {
+        return Err("Both players have a line".to_string());
+    }
+    if has_line(position.player) {
+        return Err(format!(
+            "Player {} has a line but did not move last",
+            position.player
+        ));
+    }
+    let mut moves = Vec::new();
+    if !unplay(&variant, &position, &mut HashSet::new(), &mut moves) {
+        return Err("Every order of these moves ends the game before the last one".to_string());
+    }
+    Ok(moves)
+}
+
+fn read_input(args: &[String]) -> Result<String, String> {
+    match crate::positional(args, 0) {
+        Some(text) if text != "-" => Ok(text.to_string()),
+        _ => {
+            let mut text = String::new();
+            std::io::stdin()
+                .read_to_string(&mut text)
+                .map_err(|error| format!("Could not read stdin: {}", error))?;
+            Ok(text)
+        }
+    }
+}
+
+fn parse_input(text: &str, format: Format) -> Result<Game, String> {
+    match format {
+        Format::State | Format::Moves => notation::parse_game(text.trim()),
+        Format::Grid => parse_grid(text),
+        Format::Stacks => parse_stacks(text),
+    }
+}
+
+/// Parses any of the formats, detecting which one `text` is in.
+pub fn parse_any(text: &str) -> Result<Game, String> {
+    parse_input(text, Format::detect(text))
+}
+
+/// Parses any of the formats along with moves that reach the position: a move
+/// sequence's own moves, otherwise the order `moves_to` finds.
+pub fn parse_with_moves(text: &str) -> Result<(Game, Vec<usize>), String> {
+    let g = parse_any(text)?;
+    let moves = match Format::detect(text) {
+        Format::Moves => notation::parse_moves(text.trim(), 3)?,
+        _ => moves_to(&g)?,
+    };
+    Ok((g, moves))
+}
+
+/// `convert [INPUT|-] [--from state|moves|grid|stacks] [--to state|moves|grid|stacks]`
+/// `[--board LAYOUT] [--colour] [--unicode]`
+pub fn convert(args: &[String]) {
+    let result = read_input(args).and_then(|text| {
+        let style = Style::from_args(args, Layout::Digits)?;
+        let from = match crate::flag_value(args, "--from") {
+            Some(name) => Format::parse(name)?,
+            None => Format::detect(&text),
+        };
+        let to = crate::flag_value(args, "--to")
+            .map(Format::parse)
+            .transpose()?;
+        let g = parse_input(&text, from)?;
+        let moves = moves_to(&g)?;
+        Ok((g, moves, to, style))
+    });
+    let (g, moves, to, style) = match result {
+        Ok(converted) => converted,
+        Err(error) => {
+            println!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    match to {
+        Some(Format::State) => println!("{}", game_to_number(g)),
+        Some(Format::Moves) => println!("{}", notation::format_moves(&moves)),
+        Some(Format::Stacks) => println!("{}", format_stacks(&g)),
+        Some(Format::Grid) => print!("{}", game_to_str(g)),
+        None => {
+            println!("State: {}", game_to_number(g.clone()));
+            println!("Moves: {}", notation::format_moves(&moves));
+            println!("Stacks: {}", format_stacks(&g));
+            let highlights = Highlights::for_game(&g, moves.last().copied());
+            if highlights.line.is_empty() {
+                println!("Player {} to move", g.player);
+            } else {
+                println!("Player {} has won", switch_player(g.player));
+            }
+            print!("{}", render::render(&g, &style, &highlights));
+            for line in render::describe(&style, &highlights) {
+                println!("{}", line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn digit_rows_stay_moves_and_state_numbers() {
+        for text in ["1 2 1", "121", "210", "2 2 1", "111"] {
+            assert!(!starts_grid(text), "{} starts a grid", text);
+        }
+        assert!(Format::detect("1 2 1") == Format::Moves);
+        assert!(Format::detect("121") == Format::State);
+        assert!(Format::detect("210") == Format::State);
+        let g = notation::parse_game("1 2 1").unwrap();
+        assert_eq!(g.board.data[0][0][..2], [1, 1]);
+        assert_eq!(g.board.data[0][1][0], 2);
+        assert_eq!(game_to_number(notation::parse_game("210").unwrap()), 210);
+    }
+
+    #[test]
+    fn grids_need_a_marker_or_the_prefix() {
+        assert!(starts_grid("X . O"));
+        assert!(starts_grid("..."));
+        assert!(starts_grid("grid"));
+        let digits = "grid\n000\n010\n000\n\n000\n000\n000\n\n000\n000\n000\n";
+        assert!(Format::detect(digits) == Format::Grid);
+        let g = parse_grid(digits).unwrap();
+        assert_eq!(g.board.data[1][1][0], 1);
+        let marked = digits
+            .replace("grid\n", "")
+            .replace('1', "X")
+            .replace('0', ".");
+        assert!(starts_grid(marked.lines().next().unwrap()));
+        let same = parse_grid(&marked).unwrap();
+        assert_eq!(game_to_number(same), game_to_number(g));
+    }
+}
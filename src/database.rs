@@ -0,0 +1,131 @@
+use std::fs::File;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Where `minimax_tree` writes its output.
+pub const DEFAULT_PATH: &str = "sorted_output.bin";
+
+/// Each record is an 8 byte little endian state number, the stored move and the winner.
+pub const RECORD_SIZE: u64 = 10;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(
+    feature = "python",
+    pyo3::pyclass(get_all, frozen, module = "connect3")
+)]
+pub struct Record {
+    pub state: u64,
+    /// Index `x + 3y + 9z` of the piece placed by the chosen move, -1 if the game is over.
+    pub stored_move: i8,
+    /// 1 or 2 for the player that wins under perfect play, 0 for a draw.
+    pub winner: i8,
+}
+
+impl Record {
+    fn from_bytes(buffer: &[u8; RECORD_SIZE as usize]) -> Record {
+        let mut int64_bytes: [u8; 8] = Default::default();
+        int64_bytes.copy_from_slice(&buffer[0..8]);
+        Record {
+            state: u64::from_le_bytes(int64_bytes),
+            stored_move: buffer[8] as i8,
+            winner: buffer[9] as i8,
+        }
+    }
+}
+
+/// Read only handle on a sorted output file. Lookups go straight to disk so the
+/// 5 GB table never has to be loaded.
+pub struct Database {
+    path: PathBuf,
+    file: Mutex<File>,
+    len: u64,
+}
+
+impl Database {
+    pub fn open<P: AsRef<Path>>(path: P) -> io::Result<Database> {
+        let file = File::open(path.as_ref())?;
+        let bytes = file.metadata()?.len();
+        if bytes % RECORD_SIZE != 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "{} is {} bytes, not a whole number of {} byte records",
+                    path.as_ref().display(),
+                    bytes,
+                    RECORD_SIZE
+                ),
+            ));
+        }
+        Ok(Database {
+            path: path.as_ref().to_path_buf(),
+            file: Mutex::new(file),
+            len: bytes / RECORD_SIZE,
+        })
+    }
+
+    /// Number of records in the file.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn record_at(file: &mut File, index: u64) -> io::Result<Record> {
+        file.seek(SeekFrom::Start(index * RECORD_SIZE))?;
+        let mut buffer = [0u8; RECORD_SIZE as usize];
+        file.read_exact(&mut buffer)?;
+        Ok(Record::from_bytes(&buffer))
+    }
+
+    /// Binary search for `state_num`.
+    pub fn lookup(&self, state_num: u64) -> io::Result<Option<Record>> {
+        let mut file = self.file.lock().unwrap();
+        let mut low: i64 = -1;
+        let mut high: i64 = self.len as i64;
+        while high > low + 1 {
+            let mid: i64 = (low + high) / 2;
+            let record = Database::record_at(&mut file, mid as u64)?;
+            if record.state == state_num {
+                return Ok(Some(record));
+            } else if record.state > state_num {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        Ok(None)
+    }
+
+    /// Streams every record in file order through its own buffered handle.
+    pub fn records(&self) -> io::Result<Records> {
+        Ok(Records {
+            reader: BufReader::with_capacity(1 << 20, File::open(&self.path)?),
+            remaining: self.len,
+        })
+    }
+}
+
+pub struct Records {
+    reader: BufReader<File>,
+    remaining: u64,
+}
+
+impl Iterator for Records {
+    type Item = io::Result<Record>;
+
+    fn next(&mut self) -> Option<io::Result<Record>> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let mut buffer = [0u8; RECORD_SIZE as usize];
+        Some(
+            self.reader
+                .read_exact(&mut buffer)
+                .map(|_| Record::from_bytes(&buffer)),
+        )
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt;
+use std::time::Instant;
+
+use crate::database::{self, Database};
+use crate::negamax::{Searcher, WIN};
+use crate::notation;
+use crate::player::{self, Rng};
+use crate::variant::{Position, Variant};
+
+const DEFAULT_SAMPLES: usize = 200;
+const DEFAULT_DEPTH: u32 = 2;
+
+/// Static scores stay well inside the forced results, see `negamax::is_proven`.
+const MAX_SCORE: i32 = WIN / 2;
+
+/// Scores a position for the side to move where the search stops. Each weight
+/// counts for the side to move and against the opponent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Evaluator {
+    /// Per line with no enemy pieces, times the square of the pieces in it
+    pub line: i32,
+    /// Per line one piece short whose gap cannot be filled yet
+    pub threat: i32,
+    /// Per line one piece short whose gap can be filled on the next move
+    pub immediate: i32,
+    /// Extra per threat with its gap on a row that suits its owner: odd rows,
+    /// counted from 1 at the bottom, for the first player and even rows for the
+    /// second
+    pub parity: i32,
+    /// Per piece, times the lines through its cell, which favours the centre
+    pub centre: i32,
+}
+
+impl Default for Evaluator {
+    fn default() -> Evaluator {
+        Evaluator {
+            line: 1,
+            threat: 8,
+            immediate: 30,
+            parity: 4,
+            centre: 1,
+        }
+    }
+}
+
+impl fmt::Display for Evaluator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "line={},threat={},immediate={},parity={},centre={}",
+            self.line, self.threat, self.immediate, self.parity, self.centre
+        )
+    }
+}
+
+impl Evaluator {
+    /// Changes the defaults named in a list like `threat=10,parity=0`.
+    pub fn parse(text: &str) -> Result<Evaluator, String> {
+        let mut evaluator = Evaluator::default();
+        for part in text.split(',').filter(|part| !part.is_empty()) {
+            let (name, value) = part
+                .split_once('=')
+                .ok_or(format!("Weights are written name=value, not {}", part))?;
+            let value: i32 = value.parse().map_err(|_| format!("Bad weight: {}", part))?;
+            match name {
+                "line" => evaluator.line = value,
+                "threat" => evaluator.threat = value,
+                "immediate" => evaluator.immediate = value,
+                "parity" => evaluator.parity = value,
+                "centre" => evaluator.centre = value,
+                _ => {
+                    return Err(format!(
+                        "Unknown weight {}, use line, threat, immediate, parity or centre",
+                        name
+                    ))
+                }
+            }
+        }
+        Ok(evaluator)
+    }
+
+    /// One side's score, `side` being 0 for the first player.
+    fn side_score(&self, variant: &Variant, position: &Position, side: usize) -> i32 {
+        let own = position.pieces[side];
+        let other = position.pieces[1 - side];
+        let layer = variant.size * variant.size;
+        let mut score = 0;
+        for &line in variant.lines() {
+            let count = (line & own).count_ones() as usize;
+            if line & other != 0 || count == 0 {
+                continue;
+            }
+            if count + 1 < variant.connect {
+                score += self.line * (count * count) as i32;
+                continue;
+            }
+            let gap = (line & !own).trailing_zeros() as usize;
+            let z = gap / layer;
+            if position.heights[variant.peg_of(gap)] as usize == z {
+                score += self.immediate;
+            } else {
+                score += self.threat;
+                if z.is_multiple_of(2) == (side == 0) {
+                    score += self.parity;
+                }
+            }
+        }
+        let mut pieces = own;
+        while pieces != 0 {
+            let cell = pieces.trailing_zeros() as usize;
+            score += self.centre * variant.lines_through(cell).len() as i32;
+            pieces &= pieces - 1;
+        }
+        score
+    }
+
+    /// From the side to move's point of view, for a position still in play.
+    pub fn evaluate(&self, variant: &Variant, position: &Position) -> i32 {
+        let side = (position.player - 1) as usize;
+        let score =
+            self.side_score(variant, position, side) - self.side_score(variant, position, 1 - side);
+        score.clamp(-MAX_SCORE, MAX_SCORE)
+    }
+}
+
+/// `--weights SPEC` on top of the defaults.
+pub fn evaluator_flag(args: &[String]) -> Result<Evaluator, String> {
+    crate::flag_value(args, "--weights").map_or(Ok(Evaluator::default()), Evaluator::parse)
+}
+
+/// The database's winner after the side to move plays `peg`, None if the
+/// position it leads to is missing.
+fn winner_after(
+    database: &Database,
+    variant: &Variant,
+    position: &Position,
+    peg: usize,
+) -> Result<Option<i8>, String> {
+    let mut next = *position;
+    match variant.play(&mut next, peg) {
+        None => return Err(format!("Peg {} is full", notation::format_peg(peg))),
+        Some(true) => return Ok(Some(position.player)),
+        Some(false) if variant.is_full(&next) => return Ok(Some(0)),
+        Some(false) => {}
+    }
+    lookup(database, &next)
+}
+
+fn lookup(database: &Database, position: &Position) -> Result<Option<i8>, String> {
+    match database.lookup(position.state_number()) {
+        Ok(record) => Ok(record.map(|record| record.winner)),
+        Err(error) => Err(format!("Could not read the database: {}", error)),
+    }
+}
+
+/// Plays a random number of random moves from `start`, or None if the game ends
+/// on the way.
+fn random_position(variant: &Variant, start: &Position, rng: &mut Rng) -> Option<Position> {
+    let remaining = variant.cells() - start.moves as usize;
+    let mut position = *start;
+    for _ in 0..rng.below(remaining) {
+        let pegs: Vec<usize> = (0..variant.pegs())
+            .filter(|&peg| variant.can_play(&position, peg))
+            .collect();
+        let peg = pegs[rng.below(pegs.len())];
+        if variant.play(&mut position, peg)? {
+            return None;
+        }
+    }
+    (!variant.is_full(&position)).then_some(position)
+}
+
+/// Whether one search kept the database's result and called the winner.
+struct Verdict {
+    keeps: bool,
+    calls: bool,
+}
+
+/// Searches `position`, whose winner is `winner`, and judges the result. None
+/// when the position the search's move leads to is missing from the database.
+fn judge(
+    searcher: &mut Searcher,
+    depth: u32,
+    database: &Database,
+    variant: &Variant,
+    position: &Position,
+    winner: i8,
+) -> Result<Option<Verdict>, String> {
+    let result = searcher.solve(position, Some(depth));
+    let peg = result.best_peg.ok_or("The search found no move")?;
+    let after = match winner_after(database, variant, position, peg)? {
+        Some(after) => after,
+        None => return Ok(None),
+    };
+    let opponent = if position.player == 1 { 2 } else { 1 };
+    let called = match result.score {
+        0 => 0,
+        score if score > 0 => position.player,
+        _ => opponent,
+    };
+    Ok(Some(Verdict {
+        keeps: after == winner,
+        calls: called == winner,
+    }))
+}
+
+/// How often a search keeps the database's result and calls the winner.
+#[derive(Default)]
+struct Agreement {
+    moves: usize,
+    outcomes: usize,
+}
+
+impl Agreement {
+    fn add(&mut self, verdict: Verdict) {
+        self.moves += verdict.keeps as usize;
+        self.outcomes += verdict.calls as usize;
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let variant = Variant::new(3, 3).unwrap();
+    let start = notation::position_arg(args, &variant)?;
+    let samples = match crate::flag_value(args, "--samples") {
+        Some(samples) => samples
+            .parse()
+            .map_err(|_| format!("Bad number of samples: {}", samples))?,
+        None => DEFAULT_SAMPLES,
+    };
+    let depth = match crate::flag_value(args, "--depth") {
+        Some(depth) => depth.parse().map_err(|_| format!("Bad depth: {}", depth))?,
+        None => DEFAULT_DEPTH,
+    };
+    if samples == 0 || depth == 0 {
+        return Err("Use at least one sample and a depth of at least 1".to_string());
+    }
+    if variant.winner(&start).is_some() || variant.is_full(&start) {
+        return Err("The game is over".to_string());
+    }
+    let evaluator = evaluator_flag(args)?;
+    let seed = player::seed(args)?;
+    let path = crate::flag_value(args, "--database").unwrap_or(database::DEFAULT_PATH);
+    let database =
+        Database::open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+
+    println!("Weights: {}", evaluator);
+    println!("Depth {}, {} samples, seed {}", depth, samples, seed);
+    let started = Instant::now();
+    let mut rng = Rng::new(seed);
+    let mut with_eval = Searcher::new(&variant, 16).with_evaluator(evaluator);
+    let mut without_eval = Searcher::new(&variant, 16);
+    let mut evaluated = Agreement::default();
+    let mut baseline = Agreement::default();
+    let mut done = 0;
+    let mut missing = 0;
+    // Random games from a position in the database stay in the database, so
+    // misses only come from files that hold part of the tree. Positions whose
+    // searched moves leave the file are skipped the same way
+    for _ in 0..samples * 20 {
+        if done == samples {
+            break;
+        }
+        let position = match random_position(&variant, &start, &mut rng) {
+            Some(position) => position,
+            None => continue,
+        };
+        let winner = match lookup(&database, &position)? {
+            Some(winner) => winner,
+            None => {
+                missing += 1;
+                continue;
+            }
+        };
+        let judged = (
+            judge(
+                &mut with_eval,
+                depth,
+                &database,
+                &variant,
+                &position,
+                winner,
+            )?,
+            judge(
+                &mut without_eval,
+                depth,
+                &database,
+                &variant,
+                &position,
+                winner,
+            )?,
+        );
+        match judged {
+            (Some(with), Some(without)) => {
+                evaluated.add(with);
+                baseline.add(without);
+                done += 1;
+            }
+            _ => missing += 1,
+        }
+    }
+    if done == 0 {
+        return Err(format!("None of the sampled positions are in {}", path));
+    }
+    if missing > 0 {
+        println!(
+            "Skipped {} positions missing from {} or moving out of it",
+            missing, path
+        );
+    }
+
+    let percent = |count: usize| 100.0 * count as f64 / done as f64;
+    println!("{:<18} {:>10} {:>10}", "", "Evaluator", "Flat");
+    println!(
+        "{:<18} {:>9.1}% {:>9.1}%",
+        "Keeps the result",
+        percent(evaluated.moves),
+        percent(baseline.moves)
+    );
+    println!(
+        "{:<18} {:>9.1}% {:>9.1}%",
+        "Calls the winner",
+        percent(evaluated.outcomes),
+        percent(baseline.outcomes)
+    );
+    println!("{} positions in {:.2?}", done, started.elapsed());
+    Ok(())
+}
+
+/// `calibrate [POSITION] [--samples N] [--depth D] [--weights SPEC] [--seed N]`
+/// `[--database PATH]`
+///
+/// Compares depth limited searches with and without the evaluator against the
+/// database on positions reached by random play.
+pub fn calibrate(args: &[String]) {
+    if let Err(error) = run(args) {
+        println!("{}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(moves: &str) -> Position {
+        let variant = Variant::new(3, 3).unwrap();
+        notation::play_moves(&variant, &notation::parse_moves(moves, 3).unwrap()).unwrap()
+    }
+
+    fn only(threat: i32, immediate: i32, parity: i32) -> Evaluator {
+        Evaluator {
+            line: 0,
+            threat,
+            immediate,
+            parity,
+            centre: 0,
+        }
+    }
+
+    #[test]
+    fn immediate_threats_outweigh_delayed_ones() {
+        let variant = Variant::new(3, 3).unwrap();
+        let evaluator = only(8, 30, 0);
+        // Player 1 can finish the bottom row on peg 3 straight away, with
+        // player 2 to move
+        assert_eq!(evaluator.evaluate(&variant, &position("1 5 2")), -30);
+        // Player 1's gap on peg 7 waits for a piece below it
+        assert_eq!(evaluator.evaluate(&variant, &position("9 8 9 9 8 6")), 8);
+    }
+
+    #[test]
+    fn parity_favours_odd_rows_for_the_first_player() {
+        let variant = Variant::new(3, 3).unwrap();
+        let evaluator = only(0, 0, 4);
+        // Player 1 to move in all three
+        assert_eq!(evaluator.evaluate(&variant, &position("9 9 9 5 5 1")), 4);
+        assert_eq!(evaluator.evaluate(&variant, &position("9 8 9 9 8 6")), 0);
+        assert_eq!(evaluator.evaluate(&variant, &position("9 9 4 9 2 7")), -4);
+    }
+
+    #[test]
+    fn depth_one_search_takes_the_win() {
+        let variant = Variant::new(3, 3).unwrap();
+        let mut searcher = Searcher::new(&variant, 1).with_evaluator(Evaluator::default());
+        let result = searcher.solve(&position("1 5 2 9"), Some(1));
+        assert_eq!(result.best_peg, Some(2));
+        assert!(result.score > MAX_SCORE);
+    }
+}
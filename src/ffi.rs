@@ -0,0 +1,198 @@
+//! C ABI over the rules and the database. `include/connect3.h` is generated from
+//! this file by cbindgen and `examples/ffi.c` uses it.
+//!
+//! Cells are passed as 27 bytes indexed `x * 9 + y * 3 + z`, with x going Down,
+//! y going Right and z up the peg, holding 0 for empty or the player's number.
+//! Pegs are numbered 1 to 9 as in the move notation.
+
+use std::ffi::{c_char, c_int, CStr};
+use std::ptr;
+
+use crate::database::Database;
+use crate::{
+    board_to_number, get_all_next_numbers, is_full, is_over, make_new_board, number_to_board,
+    place_new_piece, stored_move_to_human_move, STATES,
+};
+
+/// Whether a line of three has been made in `state`, 1 or 0.
+#[no_mangle]
+pub extern "C" fn connect3_is_over(state: u64) -> c_int {
+    c_int::from(state < STATES && is_over(&number_to_board(state)))
+}
+
+/// Whether every cell of `state` is taken, 1 or 0.
+#[no_mangle]
+pub extern "C" fn connect3_is_full(state: u64) -> c_int {
+    c_int::from(state < STATES && is_full(&number_to_board(state)))
+}
+
+/// The state after the side to move plays `peg`, or -1 if the peg is full, the
+/// game is over or either argument is out of range.
+#[no_mangle]
+pub extern "C" fn connect3_play(state: u64, peg: c_int) -> i64 {
+    if state >= STATES || !(1..=9).contains(&peg) {
+        return -1;
+    }
+    let peg = (peg - 1) as usize;
+    let mut g = number_to_board(state);
+    if is_over(&g) {
+        return -1;
+    }
+    match place_new_piece(&mut g.board, peg / 3, peg % 3, g.player) {
+        Some(_) => board_to_number(&g.board) as i64,
+        None => -1,
+    }
+}
+
+/// Writes the cells of `state` to `cells` and returns the player to move, or -1
+/// if the state is out of range.
+///
+/// # Safety
+///
+/// `cells` must point to 27 writable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn connect3_number_to_board(state: u64, cells: *mut i8) -> c_int {
+    if state >= STATES || cells.is_null() {
+        return -1;
+    }
+    let g = number_to_board(state);
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                *cells.add(x * 9 + y * 3 + z) = g.board.data[x][y][z];
+            }
+        }
+    }
+    c_int::from(g.player)
+}
+
+/// The state number of 27 cells, or -1 if a cell is not 0, 1 or 2.
+///
+/// # Safety
+///
+/// `cells` must point to 27 readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn connect3_board_to_number(cells: *const i8) -> i64 {
+    if cells.is_null() {
+        return -1;
+    }
+    let mut board = make_new_board();
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                let cell = *cells.add(x * 9 + y * 3 + z);
+                if !(0..=2).contains(&cell) {
+                    return -1;
+                }
+                board.data[x][y][z] = cell;
+            }
+        }
+    }
+    board_to_number(&board) as i64
+}
+
+/// Writes up to `capacity` of the states the side to move can reach from
+/// `state` to `next` and returns how many there are, at most 9. Nothing is
+/// generated once the game is over.
+///
+/// # Safety
+///
+/// `next` must point to `capacity` writable numbers, or be null when
+/// `capacity` is 0.
+#[no_mangle]
+pub unsafe extern "C" fn connect3_next_numbers(
+    state: u64,
+    next: *mut u64,
+    capacity: usize,
+) -> usize {
+    if state >= STATES {
+        return 0;
+    }
+    let g = number_to_board(state);
+    if is_over(&g) {
+        return 0;
+    }
+    let numbers = get_all_next_numbers(g);
+    if !next.is_null() {
+        for (i, &number) in numbers.iter().take(capacity).enumerate() {
+            *next.add(i) = number;
+        }
+    }
+    numbers.len()
+}
+
+/// Opens a solved database, or returns null if it cannot be read. Close it with
+/// `connect3_database_close`.
+///
+/// # Safety
+///
+/// `path` must be a null terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn connect3_database_open(path: *const c_char) -> *mut Database {
+    if path.is_null() {
+        return ptr::null_mut();
+    }
+    let path = match CStr::from_ptr(path).to_str() {
+        Ok(path) => path,
+        Err(_) => return ptr::null_mut(),
+    };
+    match Database::open(path) {
+        Ok(database) => Box::into_raw(Box::new(database)),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `database` must come from `connect3_database_open` and not be used again.
+/// Null is ignored.
+#[no_mangle]
+pub unsafe extern "C" fn connect3_database_close(database: *mut Database) {
+    if !database.is_null() {
+        drop(Box::from_raw(database));
+    }
+}
+
+/// Looks up the stored move and the winner under perfect play (0 for a draw)
+/// for `state`. `peg` is 0 for finished games. Returns 1 when the state is in
+/// the database, 0 when it is not and -1 if the file cannot be read. The handle
+/// may be shared between threads.
+///
+/// # Safety
+///
+/// `database` must be an open handle, and `peg` and `winner` must each be null
+/// or point to a writable byte.
+#[no_mangle]
+pub unsafe extern "C" fn connect3_best_move(
+    database: *const Database,
+    state: u64,
+    peg: *mut i8,
+    winner: *mut i8,
+) -> c_int {
+    let database = match database.as_ref() {
+        Some(database) => database,
+        None => return -1,
+    };
+    match database.lookup(state) {
+        Ok(Some(record)) => {
+            if !peg.is_null() {
+                *peg = stored_move_to_human_move(record.stored_move) + 1;
+            }
+            if !winner.is_null() {
+                *winner = record.winner;
+            }
+            1
+        }
+        Ok(None) => 0,
+        Err(_) => -1,
+    }
+}
+
+/// The player to move in `state`, or -1 if it is out of range.
+#[no_mangle]
+pub extern "C" fn connect3_player_to_move(state: u64) -> c_int {
+    if state >= STATES {
+        return -1;
+    }
+    c_int::from(number_to_board(state).player)
+}
@@ -0,0 +1,132 @@
+use crate::play::{self, Engine};
+use crate::render::{self, Highlights, Layout, Style};
+use crate::variant::{Position, Variant};
+use crate::{
+    game_to_number, get_all_next_numbers, get_move_between_board, is_full, is_over, notation,
+    number_to_board, place_new_piece, stored_move_to_human_move, switch_player, Game,
+};
+
+const RESET: &str = "\x1b[0m";
+const WIN_COLOUR: &str = "\x1b[32m";
+const DRAW_COLOUR: &str = "\x1b[33m";
+const LOSS_COLOUR: &str = "\x1b[31m";
+
+#[derive(Clone, Copy)]
+enum Outcome {
+    Full,
+    /// The winner under perfect play after the move, 0 for a draw, and the
+    /// moves until the game ends counting this one when known
+    Result(i8, Option<u32>),
+}
+
+/// The outcome of every peg for the side to move. The database only knows the
+/// length of immediate wins; the search knows every length.
+fn outcomes(engine: &mut Engine, g: &Game) -> Result<[Outcome; 9], String> {
+    let mut outcomes = [Outcome::Full; 9];
+    match engine {
+        Engine::Database(database) => {
+            let state_num = game_to_number(g.clone());
+            for next_number in get_all_next_numbers(g.clone()) {
+                let cell = get_move_between_board(state_num, next_number);
+                let peg = stored_move_to_human_move(cell) as usize;
+                outcomes[peg] = if is_over(&number_to_board(next_number)) {
+                    Outcome::Result(g.player, Some(1))
+                } else {
+                    match database.lookup(next_number) {
+                        Ok(Some(record)) => Outcome::Result(record.winner, None),
+                        Ok(None) => {
+                            return Err(format!("State {} is not in the database", next_number))
+                        }
+                        Err(error) => {
+                            return Err(format!("Could not read the database: {}", error))
+                        }
+                    }
+                };
+            }
+        }
+        Engine::Search(searcher) => {
+            for (peg, outcome) in outcomes.iter_mut().enumerate() {
+                let mut next = g.clone();
+                if place_new_piece(&mut next.board, peg / 3, peg % 3, g.player).is_none() {
+                    continue;
+                }
+                next.player = switch_player(g.player);
+                *outcome = if is_over(&next) {
+                    Outcome::Result(g.player, Some(1))
+                } else if is_full(&next) {
+                    Outcome::Result(0, Some(1))
+                } else {
+                    let position = Position::from_game(&next);
+                    let result = searcher.solve(&position, None);
+                    let winner = result
+                        .winner(&position)
+                        .ok_or("The search did not finish".to_string())?;
+                    Outcome::Result(winner, result.moves_to_end(&position).map(|m| m + 1))
+                };
+            }
+        }
+    }
+    Ok(outcomes)
+}
+
+fn cell(outcome: Outcome, player: i8, colour: bool) -> String {
+    let (text, code) = match outcome {
+        Outcome::Full => ("full".to_string(), ""),
+        Outcome::Result(winner, moves) => {
+            let (word, code) = if winner == 0 {
+                ("draw", DRAW_COLOUR)
+            } else if winner == player {
+                ("win", WIN_COLOUR)
+            } else {
+                ("loss", LOSS_COLOUR)
+            };
+            match moves {
+                Some(moves) => (format!("{} {}", word, moves), code),
+                None => (word.to_string(), code),
+            }
+        }
+    };
+    // Padded before colouring so the escape codes do not count towards the width
+    let text = format!("{:<9}", text);
+    if colour && !code.is_empty() {
+        format!("{}{}{}", code, text, RESET)
+    } else {
+        text
+    }
+}
+
+fn show(args: &[String]) -> Result<(), String> {
+    let variant = Variant::new(3, 3).unwrap();
+    let style = Style::from_args(args, Layout::Layers)?;
+    let g = match crate::positional(args, 0) {
+        Some(text) => notation::parse_game(text)?,
+        None => number_to_board(0),
+    };
+    render::print(&g, &style, &Highlights::for_game(&g, None));
+    if is_over(&g) || is_full(&g) {
+        println!("The game is over");
+        return Ok(());
+    }
+    let mut engine = play::open_engine(args, &variant)?;
+    let outcomes = outcomes(&mut engine, &g)?;
+    println!(
+        "Player {} to move, results after each peg (with moves to the end when known)",
+        g.player
+    );
+    println!("   {:<9}{:<9}c", "a", "b");
+    for x in 0..3 {
+        let cells: Vec<String> = (0..3)
+            .map(|y| cell(outcomes[x * 3 + y], g.player, style.colour))
+            .collect();
+        println!("{}  {}", x + 1, cells.concat().trim_end());
+    }
+    Ok(())
+}
+
+/// `heatmap [POSITION] [--engine database|search] [--database PATH] [--board LAYOUT]`
+/// `[--colour] [--unicode]`
+pub fn heatmap(args: &[String]) {
+    if let Err(error) = show(args) {
+        println!("{}", error);
+    }
+}
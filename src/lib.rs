@@ -0,0 +1,334 @@
+//! The game rules and the solved database, shared by the `Connect3` binary and
+//! the C bindings in `ffi`.
+
+pub mod database;
+pub mod ffi;
+#[cfg(feature = "python")]
+mod python;
+pub mod stats;
+pub mod variant;
+
+/// One past the largest state number, 3^27.
+pub const STATES: u64 = 7_625_597_484_987;
+
+#[derive(Hash, Eq, PartialEq, Debug, Clone)]
+pub struct Board {
+    pub data: [[[i8; 3]; 3]; 3],
+}
+
+#[derive(Hash, Eq, PartialEq, Clone)]
+pub struct Game {
+    pub board: Board,
+    pub player: i8,
+}
+
+pub fn make_new_board() -> Board {
+    Board {
+        data: [[[0; 3]; 3]; 3],
+    }
+}
+
+pub fn switch_player(player: i8) -> i8 {
+    if player == 1 {
+        2
+    } else {
+        1
+    }
+}
+
+fn get_top(g: Board, x: usize, y: usize) -> Option<usize> {
+    for (i, &value) in g.data[x][y].iter().enumerate() {
+        if value == 0 {
+            return Some(i);
+        }
+    }
+    None
+}
+
+pub fn place_new_piece(g: &mut Board, x: usize, y: usize, player: i8) -> Option<usize> {
+    let z: Option<usize> = get_top(Board { data: g.data }, x, y);
+    if let Some(unwrapped) = z {
+        g.data[x][y][unwrapped] = player;
+    }
+    z
+}
+
+pub fn get_all_next_states(g: Board, player: i8) -> Vec<Board> {
+    let mut output: Vec<Board> = Vec::new();
+    for x in 0..3 {
+        for y in 0..3 {
+            let mut new_board = g.clone();
+            let worked = place_new_piece(&mut new_board, x, y, player);
+            if worked.is_some() {
+                output.push(new_board);
+            }
+        }
+    }
+    output
+}
+
+fn _get_num_open_spaces(g: &Game) -> i8 {
+    let mut output: i8 = 0;
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                if g.board.data[x][y][z] == 0 {
+                    output += 1;
+                }
+            }
+        }
+    }
+    output
+}
+
+#[allow(clippy::too_many_arguments)]
+fn check_win_direction(
+    g: &Game,
+    x: i8,
+    y: i8,
+    z: i8,
+    dirx: i8,
+    diry: i8,
+    dirz: i8,
+    left: i8,
+    last: i8,
+) -> bool {
+    if left == 0 {
+        return false;
+    }
+    if !(0..=3).contains(&x) || !(0..=3).contains(&y) || !(0..=3).contains(&z) {
+        return false;
+    }
+    if g.board.data[x as usize][y as usize][z as usize] == 0 {
+        return false;
+    }
+    if g.board.data[x as usize][y as usize][z as usize] == last || left == 3 {
+        if left == 1 {
+            return true;
+        } else if left == 3 {
+            return check_win_direction(
+                g,
+                x + dirx,
+                y + diry,
+                z + dirz,
+                dirx,
+                diry,
+                dirz,
+                left - 1,
+                g.board.data[x as usize][y as usize][z as usize],
+            );
+        } else {
+            return check_win_direction(
+                g,
+                x + dirx,
+                y + diry,
+                z + dirz,
+                dirx,
+                diry,
+                dirz,
+                left - 1,
+                last,
+            );
+        }
+    }
+    false
+}
+
+pub fn is_over(g: &Game) -> bool {
+    for y in 0..3 {
+        for z in 0..3 {
+            if check_win_direction(g, 0, y, z, 1, 0, 0, 3, -1) {
+                return true;
+            }
+        }
+    }
+    for x in 0..3 {
+        for z in 0..3 {
+            if check_win_direction(g, x, 0, z, 0, 1, 0, 3, -1) {
+                return true;
+            }
+        }
+    }
+    for x in 0..3 {
+        for y in 0..3 {
+            if check_win_direction(g, x, y, 0, 0, 0, 1, 3, -1) {
+                return true;
+            }
+        }
+    }
+    for x in 0..3 {
+        if check_win_direction(g, x, 0, 0, 0, 1, 1, 3, -1) {
+            return true;
+        }
+        if check_win_direction(g, x, 2, 2, 0, -1, -1, 3, -1) {
+            return true;
+        }
+    }
+    for y in 0..3 {
+        if check_win_direction(g, 0, y, 0, 1, 0, 1, 3, -1) {
+            return true;
+        }
+        if check_win_direction(g, 2, y, 2, -1, 0, -1, 3, -1) {
+            return true;
+        }
+    }
+    for z in 0..3 {
+        if check_win_direction(g, 0, 0, z, 1, 1, 0, 3, -1) {
+            return true;
+        }
+        if check_win_direction(g, 2, 2, z, -1, -1, 0, 3, -1) {
+            return true;
+        }
+    }
+    if check_win_direction(g, 0, 0, 0, 1, 1, 1, 3, -1) {
+        return true;
+    }
+    if check_win_direction(g, 0, 2, 0, 1, -1, 1, 3, -1) {
+        return true;
+    }
+    if check_win_direction(g, 2, 0, 0, -1, 1, 1, 3, -1) {
+        return true;
+    }
+    if check_win_direction(g, 2, 2, 0, -1, -1, 1, 3, -1) {
+        return true;
+    }
+    false
+}
+
+pub fn game_to_number(g: Game) -> u64 {
+    let mut output: u64 = 0;
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                output += (g.board.data[x][y][z] as u64) * 3_u64.pow((x + 3 * y + 9 * z) as u32);
+            }
+        }
+    }
+    output
+}
+
+pub fn board_to_number(g: &Board) -> u64 {
+    let mut output: u64 = 0;
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                output += (g.data[x][y][z] as u64) * 3_u64.pow((x + 3 * y + 9 * z) as u32);
+            }
+        }
+    }
+    output
+}
+
+pub fn number_to_board(mut num: u64) -> Game {
+    let mut output: Game = Game {
+        board: make_new_board(),
+        player: 1,
+    };
+    let mut pieces: i8 = 0;
+    for power in 0..27 {
+        let value = (num % 3) as i8;
+        if value != 0 {
+            pieces += 1;
+        }
+        output.board.data[power % 3][(power / 3) % 3][power / 9] = value;
+        num -= num % 3;
+        num /= 3;
+    }
+    output.player = (pieces % 2) + 1;
+    output
+}
+
+pub fn get_all_next_numbers(g: Game) -> Vec<u64> {
+    get_all_next_states(g.board, g.player)
+        .iter()
+        .map(board_to_number)
+        .collect()
+}
+
+pub fn get_move_between_board(b1: u64, b2: u64) -> i8 {
+    let mut diff = b2 - b1;
+    if diff.is_multiple_of(2) {
+        diff /= 2;
+    }
+    // Counted exactly, ln(3^p) / ln(3) lands just under p for several cells
+    let mut power: i8 = 0;
+    while diff > 1 {
+        diff /= 3;
+        power += 1;
+    }
+    power
+}
+
+pub fn stored_move_to_next_number(state_num: u64, player: i8, stored_move: i8) -> Option<u64> {
+    if !(0..27).contains(&stored_move) {
+        return None;
+    }
+    Some(state_num + (player as u64) * 3_u64.pow(stored_move as u32))
+}
+
+pub fn is_full(g: &Game) -> bool {
+    for x in 0..3 {
+        for y in 0..3 {
+            for z in 0..3 {
+                if g.board.data[x][y][z] == 0 {
+                    return false;
+                }
+            }
+        }
+    }
+    true
+}
+
+/// The peg a stored move drops into, `Down * 3 + Right`.
+pub fn stored_move_to_human_move(stored_move: i8) -> i8 {
+    if stored_move == -1 {
+        return -1;
+    }
+    (stored_move % 3) * 3 + (stored_move / 3) % 3
+}
+
+pub fn game_to_str(g: Game) -> String {
+    let mut output: String = "".to_string();
+    for z in 0..3 {
+        for x in 0..3 {
+            let content = g.board.data[x][0][z].to_string()
+                + " "
+                + &g.board.data[x][1][z].to_string()
+                + " "
+                + &g.board.data[x][2][z].to_string()
+                + "\n";
+            output.push_str(&content);
+        }
+        output.push('\n');
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn stored_moves_map_back_to_their_peg() {
+        for peg in 0..9 {
+            let mut g = Game {
+                board: make_new_board(),
+                player: 1,
+            };
+            // Each height of the peg, with both players' pieces
+            for _ in 0..3 {
+                let before = board_to_number(&g.board);
+                place_new_piece(&mut g.board, peg / 3, peg % 3, g.player).unwrap();
+                let after = board_to_number(&g.board);
+                let stored_move = get_move_between_board(before, after);
+                assert_eq!(stored_move_to_human_move(stored_move), peg as i8);
+                assert_eq!(
+                    stored_move_to_next_number(before, g.player, stored_move),
+                    Some(after)
+                );
+                g.player = switch_player(g.player);
+            }
+        }
+        assert_eq!(stored_move_to_human_move(-1), -1);
+    }
+}
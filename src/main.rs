@@ -1,361 +1,133 @@
 use std::collections::{BTreeSet, HashMap, HashSet};
-use std::hash::Hash;
-use std::io::{Read, Seek, SeekFrom, Write};
+use std::io::Write;
 use std::sync::{Arc, Mutex};
 extern crate crossbeam;
 use crossbeam::channel::unbounded;
-use std::fs::{File, OpenOptions};
+use std::fs::File;
 
-#[derive(Hash, Eq, PartialEq, Debug, Clone)]
-struct Board {
-    data: [[[i8; 3]; 3]; 3],
-}
-
-#[derive(Hash, Eq, PartialEq, Clone)]
-struct Game {
-    board: Board,
-    player: i8,
-}
-
-fn make_new_board() -> Board {
-    Board {
-        data: [[[0; 3]; 3]; 3],
-    }
-}
-
-fn switch_player(player: i8) -> i8 {
-    if player == 1 {
-        2
-    } else {
-        1
-    }
-}
-
-fn get_top(g: Board, x: usize, y: usize) -> Option<usize> {
-    for (i, &value) in g.data[x][y].iter().enumerate() {
-        if value == 0 {
-            return Some(i);
-        }
-    }
-    None
-}
-
-fn place_new_piece(g: &mut Board, x: usize, y: usize, player: i8) -> Option<usize> {
-    let z: Option<usize> = get_top(Board { data: g.data }, x, y);
-    if z.is_some() {
-        let unwrapped = z.unwrap();
-        g.data[x][y][unwrapped] = player;
-    }
-    z
-}
-
-fn get_all_next_states(g: Board, player: i8) -> Vec<Board> {
-    let mut output: Vec<Board> = Vec::new();
-    for x in 0..3 {
-        for y in 0..3 {
-            let mut new_board = g.clone();
-            let worked = place_new_piece(&mut new_board, x, y, player);
-            if worked.is_some() {
-                output.push(new_board);
-            }
-        }
-    }
-    output
-}
-
-fn _get_num_open_spaces(g: &Game) -> i8 {
-    let mut output: i8 = 0;
-    for x in 0..3 {
-        for y in 0..3 {
-            for z in 0..3 {
-                if g.board.data[x][y][z] == 0 {
-                    output += 1;
-                }
-            }
-        }
-    }
-    return output;
-}
-
-fn check_win_direction(
-    g: &Game,
-    x: i8,
-    y: i8,
-    z: i8,
-    dirx: i8,
-    diry: i8,
-    dirz: i8,
-    left: i8,
-    last: i8,
-) -> bool {
-    if left == 0 {
-        return false;
-    }
-    if x > 3 || x < 0 || y > 3 || y < 0 || z > 3 || z < 0 {
-        return false;
-    }
-    if g.board.data[x as usize][y as usize][z as usize] == 0 {
-        return false;
-    }
-    if g.board.data[x as usize][y as usize][z as usize] == last || left == 3 {
-        if left == 1 {
-            return true;
-        } else {
-            if left == 3 {
-                return check_win_direction(
-                    g,
-                    x + dirx,
-                    y + diry,
-                    z + dirz,
-                    dirx,
-                    diry,
-                    dirz,
-                    left - 1,
-                    g.board.data[x as usize][y as usize][z as usize],
-                );
-            } else {
-                return check_win_direction(
-                    g,
-                    x + dirx,
-                    y + diry,
-                    z + dirz,
-                    dirx,
-                    diry,
-                    dirz,
-                    left - 1,
-                    last,
-                );
-            }
-        }
-    }
-    return false;
-}
+mod annotate;
+mod convert;
+mod eval;
+mod heatmap;
+mod mcts;
+mod negamax;
+mod notation;
+mod perft;
+mod play;
+mod player;
+mod pns;
+mod protocol;
+mod pv;
+mod record;
+mod render;
+mod serve;
+mod strategy;
+mod svg;
+#[cfg(test)]
+mod test_support;
+mod tournament;
+mod verify;
 
-fn is_over(g: &Game) -> bool {
-    for y in 0..3 {
-        for z in 0..3 {
-            if check_win_direction(g, 0, y, z, 1, 0, 0, 3, -1) {
-                return true;
-            }
-        }
-    }
-    for x in 0..3 {
-        for z in 0..3 {
-            if check_win_direction(g, x, 0, z, 0, 1, 0, 3, -1) {
-                return true;
-            }
-        }
-    }
-    for x in 0..3 {
-        for y in 0..3 {
-            if check_win_direction(g, x, y, 0, 0, 0, 1, 3, -1) {
-                return true;
-            }
-        }
-    }
-    for x in 0..3 {
-        if check_win_direction(g, x, 0, 0, 0, 1, 1, 3, -1) {
-            return true;
-        }
-        if check_win_direction(g, x, 2, 2, 0, -1, -1, 3, -1) {
-            return true;
-        }
-    }
-    for y in 0..3 {
-        if check_win_direction(g, 0, y, 0, 1, 0, 1, 3, -1) {
-            return true;
-        }
-        if check_win_direction(g, 2, y, 2, -1, 0, -1, 3, -1) {
-            return true;
-        }
-    }
-    for z in 0..3 {
-        if check_win_direction(g, 0, 0, z, 1, 1, 0, 3, -1) {
-            return true;
-        }
-        if check_win_direction(g, 2, 2, z, -1, -1, 0, 3, -1) {
-            return true;
-        }
-    }
-    if check_win_direction(g, 0, 0, 0, 1, 1, 1, 3, -1) {
-        return true;
-    }
-    if check_win_direction(g, 0, 2, 0, 1, -1, 1, 3, -1) {
-        return true;
-    }
-    if check_win_direction(g, 2, 0, 0, -1, 1, 1, 3, -1) {
-        return true;
-    }
-    if check_win_direction(g, 2, 2, 0, -1, -1, 1, 3, -1) {
-        return true;
-    }
-    return false;
-}
-
-fn game_to_number(g: Game) -> u64 {
-    let mut output: u64 = 0;
-    for x in 0..3 {
-        for y in 0..3 {
-            for z in 0..3 {
-                output += (g.board.data[x][y][z] as u64) * 3_u64.pow((x + 3 * y + 9 * z) as u32);
-            }
-        }
-    }
-    return output;
-}
+use connect3::database::{self, Database};
+use connect3::{
+    board_to_number, game_to_number, game_to_str, get_all_next_numbers, get_all_next_states,
+    get_move_between_board, is_full, is_over, make_new_board, number_to_board, place_new_piece,
+    stored_move_to_human_move, stored_move_to_next_number, switch_player, Board, Game, STATES,
+};
+use connect3::{stats, variant};
+use variant::Variant;
 
-fn board_to_number(g: &Board) -> u64 {
-    let mut output: u64 = 0;
-    for x in 0..3 {
-        for y in 0..3 {
-            for z in 0..3 {
-                output += (g.data[x][y][z] as u64) * 3_u64.pow((x + 3 * y + 9 * z) as u32);
-            }
+fn solver(args: &[String]) {
+    let style = match render::Style::from_args(args, render::Layout::Digits) {
+        Ok(style) => style,
+        Err(error) => {
+            println!("{}", error);
+            return;
         }
-    }
-    return output;
-}
-
-fn number_to_board(mut num: u64) -> Game {
-    let mut output: Game = Game {
-        board: make_new_board(),
-        player: 1,
     };
-    let mut pieces: i8 = 0;
-    for power in 0..27 {
-        let value = (num % 3) as i8;
-        if value != 0 {
-            pieces += 1;
+    let path = flag_value(args, "--database").unwrap_or(database::DEFAULT_PATH);
+    let database = match Database::open(path) {
+        Ok(database) => database,
+        Err(error) => {
+            println!("Could not open {}: {}", path, error);
+            return;
         }
-        output.board.data[power % 3][(power / 3) % 3][power / 9] = value;
-        num -= num % 3;
-        num /= 3;
-    }
-    output.player = (pieces % 2) + 1;
-    return output;
-}
-
-fn get_all_next_numbers(g: Game) -> Vec<u64> {
-    get_all_next_states(g.board, g.player)
-        .iter()
-        .map(|x| board_to_number(x))
-        .collect()
-}
-
-fn log_base_3(x: f64) -> f64 {
-    let result = x.ln() / 3f64.ln();
-    result
-}
-
-fn get_move_between_board(b1: u64, b2: u64) -> i8 {
-    let mut diff = b2 - b1;
-    if diff % 2 == 0 {
-        diff /= 2;
-    }
-    log_base_3(diff as f64) as i8
-}
-
-fn is_full(g: &Game) -> bool {
-    for x in 0..3 {
-        for y in 0..3 {
-            for z in 0..3 {
-                if g.board.data[x][y][z] == 0 {
-                    return false;
-                }
-            }
-        }
-    }
-    true
-}
-
-fn get_best_move(state_num: u64) -> Option<(i8, i8)> {
-    let mut file = OpenOptions::new()
-        .read(true)
-        .open("C:/Users/evana/Desktop/Connect3/output_sorted_bin.bin")
-        .unwrap();
-    let mut low: i64 = -1;
-    let mut high: i64 = 548638748;
-    while high > low + 1 {
-        let mid: i64 = (low + high) / 2;
-        file.seek(SeekFrom::Start((mid as u64) * 10)).unwrap();
-        let mut buffer = [0u8; 8];
-        file.read_exact(&mut buffer).unwrap();
-        let mut int64_bytes: [u8; 8] = Default::default();
-        int64_bytes.copy_from_slice(&buffer[0..8]);
-        let found_state_num = u64::from_le_bytes(int64_bytes);
-        if found_state_num == state_num {
-            file.seek(SeekFrom::Start((mid as u64) * 10 + 8)).unwrap();
-            let mut buffer = [0u8; 2];
-            file.read_exact(&mut buffer).unwrap();
-            let first_byte: i8 = buffer[0] as i8;
-            let second_byte: i8 = buffer[1] as i8;
-            return Some((first_byte, second_byte));
-        } else if found_state_num > state_num {
-            high = mid;
-        } else {
-            low = mid;
-        }
-    }
-    return None;
-}
-
-fn stored_move_to_human_move(stored_move: i8) -> i8 {
-    if stored_move == -1 {
-        return -1;
-    }
-    stored_move / 3
-}
-
-fn game_to_str(g: Game) -> String {
-    let mut output: String = "".to_string();
-    for z in 0..3 {
-        for x in 0..3 {
-            let content = g.board.data[x][0][z].to_string()
-                + &" "
-                + &g.board.data[x][1][z].to_string()
-                + &" "
-                + &g.board.data[x][2][z].to_string()
-                + &"\n";
-            output.push_str(&content);
-        }
-        output.push_str(&"\n");
-    }
-    return output;
-}
-
-fn solver() {
+    };
     loop {
         let mut input = String::new();
-        println!("Enter a State Number:");
+        println!(
+            "Enter a State Number, moves like 5 1 9 5, or paste a grid (grid first for digits):"
+        );
         std::io::stdin()
             .read_line(&mut input)
             .expect("Failed to read line");
-        let number: Result<i64, _> = input.trim().parse();
-        match number {
-            Ok(parsed_number) => {
-                let (output, winner) = get_best_move(parsed_number as u64).unwrap();
+        let grid = convert::starts_grid(&input);
+        if grid {
+            // Reads rows until the grid's 27 cells are in
+            let cells = |text: &str| {
+                text.trim_start()
+                    .trim_start_matches("grid")
+                    .chars()
+                    .filter(|c| !c.is_whitespace())
+                    .count()
+            };
+            let mut count = cells(&input);
+            while count < 27 {
+                let mut line = String::new();
+                if std::io::stdin().read_line(&mut line).unwrap_or(0) == 0 {
+                    break;
+                }
+                count += cells(&line);
+                input.push_str(&line);
+            }
+        }
+        let parsed = if grid {
+            convert::parse_grid(&input)
+        } else {
+            notation::parse_game(input.trim())
+        };
+        match parsed {
+            Ok(mut g) => {
+                let state_num = game_to_number(g.clone());
+                if grid || notation::is_move_sequence(input.trim()) {
+                    println!("State: {}", state_num);
+                }
+                let (output, winner) = match database.lookup(state_num) {
+                    Ok(Some(record)) => (record.stored_move, record.winner),
+                    Ok(None) => {
+                        println!("State {} is not in {}", state_num, path);
+                        continue;
+                    }
+                    Err(error) => {
+                        println!("Could not read {}: {}", path, error);
+                        continue;
+                    }
+                };
                 let output = stored_move_to_human_move(output);
-                let mut g: Game = number_to_board(parsed_number as u64);
-                if output != -1 {
-                    let _ = place_new_piece(
-                        &mut g.board,
-                        (output / 3) as usize,
-                        (output % 3) as usize,
-                        g.player,
-                    );
+                if output == -1 {
+                    println!("The game is over, Winner: {}", winner);
+                    continue;
                 }
+                let _ = place_new_piece(
+                    &mut g.board,
+                    (output / 3) as usize,
+                    (output % 3) as usize,
+                    g.player,
+                );
                 let next_number: u64 = board_to_number(&g.board);
                 println!(
-                    "Down: {}, Right: {}, Next State: {}, Winner: {}",
-                    (output / 3).to_string(),
-                    (output % 3).to_string(),
-                    next_number.to_string(),
-                    winner.to_string()
+                    "Move: {}, Down: {}, Right: {}, Next State: {}, Winner: {}",
+                    notation::describe_peg(output as usize, 3),
+                    output / 3,
+                    output % 3,
+                    next_number,
+                    winner
                 );
-                println!("{}", game_to_str(g));
+                let highlights = render::Highlights::for_game(&g, Some(output as usize));
+                render::print(&g, &style, &highlights);
             }
-            Err(_) => {
-                println!("Failed to parse an integer, quitting");
+            Err(error) => {
+                println!("{}, quitting", error);
                 break;
             }
         }
@@ -389,9 +161,9 @@ fn generate_unique() -> Arc<Mutex<Vec<u64>>> {
                                 board: next_state,
                                 player: switch_player(state_to_process.player),
                             };
-                            let mut seen_set = shared_set_clone.lock().unwrap();                     
-                            if seen_set.len() % 10000000 == 0 {
-                                println!("{} / 548,638,747", seen_set.len().to_string());
+                            let mut seen_set = shared_set_clone.lock().unwrap();
+                            if seen_set.len().is_multiple_of(10000000) {
+                                println!("{} / 548,638,747", seen_set.len());
                             }
                             let next_game_num = board_to_number(&next_game.board);
                             if seen_set.contains(&next_game_num) {
@@ -403,23 +175,21 @@ fn generate_unique() -> Arc<Mutex<Vec<u64>>> {
                         }
                     }
                 }
-                Err(_) => {
-                    match shared_set_clone.lock() {
-                        Ok(seen_set) => {
-                            println!("Lock acquired!, {}", seen_set.len().to_string());
-                            if seen_set.len() >= 548638746 {
-                                drop(work_queue_sender);
-                                break;
-                            } else {
-                                continue;
-                            }
-                        }
-                        Err(_) => {
-                            println!("Lock failed!");
+                Err(_) => match shared_set_clone.lock() {
+                    Ok(seen_set) => {
+                        println!("Lock acquired!, {}", seen_set.len());
+                        if seen_set.len() >= 548638746 {
+                            drop(work_queue_sender);
+                            break;
+                        } else {
                             continue;
                         }
                     }
-                }
+                    Err(_) => {
+                        println!("Lock failed!");
+                        continue;
+                    }
+                },
             }
         });
         handles.push(handle);
@@ -430,7 +200,7 @@ fn generate_unique() -> Arc<Mutex<Vec<u64>>> {
     }
     drop(work_queue_sender);
     println!("Done With Unique State Generation");
-    return unique_stack.clone();
+    unique_stack.clone()
 }
 
 fn minimax_tree(unique_stack: Arc<Mutex<Vec<u64>>>) {
@@ -442,8 +212,7 @@ fn minimax_tree(unique_stack: Arc<Mutex<Vec<u64>>>) {
     let work_queue_sender_clone = work_queue_sender.clone();
     let reader_handle = std::thread::spawn(move || loop {
         let mut unique_stack = unique_stack.lock().unwrap();
-        while !unique_stack.is_empty() {
-            let value: u64 = unique_stack.pop().unwrap();
+        while let Some(value) = unique_stack.pop() {
             let _ = work_queue_sender_clone.send(value);
         }
     });
@@ -488,7 +257,6 @@ fn minimax_tree(unique_stack: Arc<Mutex<Vec<u64>>>) {
                     let mut two_game: Option<u64> = None;
                     let mut tie_game: Option<u64> = None;
                     let mut chosen_game: Option<u64> = None;
-                    let mut chosen_move: i8 = -1;
                     let mut unfinished: bool = false;
                     for num in next_game_nums {
                         let gv = game_value_clone.lock().unwrap();
@@ -551,8 +319,8 @@ fn minimax_tree(unique_stack: Arc<Mutex<Vec<u64>>>) {
                         }
                         _ => panic!("Player Non Normal"),
                     }
-                    if chosen_game.is_some() {
-                        chosen_move = get_move_between_board(num_to_process, chosen_game.unwrap());
+                    let chosen_move: i8 = if let Some(chosen_game) = chosen_game {
+                        get_move_between_board(num_to_process, chosen_game)
                     } else {
                         println!(
                             "STATE: {}{}",
@@ -560,7 +328,7 @@ fn minimax_tree(unique_stack: Arc<Mutex<Vec<u64>>>) {
                             (has_one || has_two || has_tie)
                         );
                         panic!("Chose non-existent state")
-                    }
+                    };
                     output_set
                         .lock()
                         .unwrap()
@@ -599,7 +367,7 @@ fn minimax_tree(unique_stack: Arc<Mutex<Vec<u64>>>) {
     let output_set = output_set.lock().unwrap();
     for element in output_set.iter() {
         written += 1;
-        if written % 10000000 == 0 {
+        if written.is_multiple_of(10000000) {
             println!("{} / 548,638,747", written);
         }
         let _ = output_bin.write_all(&(element.0).to_le_bytes());
@@ -614,6 +382,222 @@ fn generate() {
     minimax_tree(generate_unique());
 }
 
+/// Flags that take no value.
+const SWITCHES: [&str; 11] = [
+    "--symmetric",
+    "--check",
+    "--hint",
+    "--colour",
+    "--unicode",
+    "--strip",
+    "--outcomes",
+    "--print",
+    "--no-eval",
+    "--share",
+    "--eval",
+];
+
+fn has_switch(args: &[String], name: &str) -> bool {
+    args.iter().any(|arg| arg == name)
+}
+
+fn flag_value<'a>(args: &'a [String], name: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == name)
+        .and_then(|index| args.get(index + 1))
+        .map(|value| value.as_str())
+}
+
+/// The `index`th argument after the command name that is not a flag or a flag's value.
+fn positional(args: &[String], index: usize) -> Option<&str> {
+    let mut found = 0;
+    let mut skip = true;
+    for arg in args.iter().skip(1) {
+        if skip {
+            skip = false;
+        } else if arg.starts_with("--") {
+            skip = !SWITCHES.contains(&arg.as_str());
+        } else if found == index {
+            return Some(arg);
+        } else {
+            found += 1;
+        }
+    }
+    None
+}
+
+fn variant_flag(args: &[String]) -> Result<Variant, String> {
+    let size = match flag_value(args, "--size") {
+        Some(size) => size
+            .parse()
+            .map_err(|_| format!("Bad board size: {}", size))?,
+        None => 3,
+    };
+    let connect = match flag_value(args, "--connect") {
+        Some(connect) => connect
+            .parse()
+            .map_err(|_| format!("Bad line length: {}", connect))?,
+        None => size,
+    };
+    Variant::new(size, connect)
+}
+
+fn print_usage() {
+    println!("Usage: Connect3 [command]");
+    println!("  generate            enumerate and solve every state (default)");
+    println!("  solve [--database PATH] [--board digits|layers|pegs] [--colour] [--unicode]");
+    println!("                      look up best moves by state number, move sequence or grid");
+    println!("  verify [database]   check a sorted output file for consistency");
+    println!(
+        "  search [state] [--size N] [--connect K] [--depth D] [--table-mb M] [--database PATH]"
+    );
+    println!("       [--eval] [--weights SPEC]");
+    println!("                      solve a position with alpha-beta search, or score it with");
+    println!("                      the evaluator at the depth limit");
+    println!("  prove [state] [--size N] [--connect K] [--nodes LIMIT] [--output FILE]");
+    println!("                      write a proof tree of a forced win");
+    println!("  prove --check FILE  replay a proof tree against the rules");
+    println!("  strategy [state] [--database PATH] [--output FILE] [--symmetric]");
+    println!("                      export player 1's winning moves from the database");
+    println!("  strategy --check FILE");
+    println!("                      check that a strategy file wins against every reply");
+    println!("  stats [--database PATH] [--json FILE]");
+    println!("                      positions and results by piece count");
+    println!("  perft [--depth D]   count move sequences and positions at each depth");
+    println!("  perft --check [FILE] [--depth D]");
+    println!("                      compare perft counts with fixtures/perft.txt");
+    println!("  convert [INPUT|-] [--from FORMAT] [--to FORMAT]");
+    println!("                      convert between state, moves, grid and stacks");
+    println!("  pv [POSITION] [--engine database|search] [--database PATH] [--board LAYOUT]");
+    println!("                      play out the perfect game from a position");
+    println!("  heatmap [POSITION] [--engine database|search] [--database PATH] [--colour]");
+    println!("                      result of playing each peg");
+    println!("  svg [POSITION] [--output FILE] [--strip] [--columns N] [--outcomes]");
+    println!("                      draw a position or a game as SVG diagrams");
+    println!("  play [--side 1|2] [--engine NAME] [--player1 NAME --player2 NAME] [--seed N]");
+    println!("       [--database PATH] [--record FILE]");
+    println!("                      play against an engine, or watch two players; players are");
+    println!("                      human, random, greedy, database, search or mcts");
+    println!("  versus [FILE] [--hint] [--engine database|search] [--database PATH]");
+    println!("                      two player game with undo, redo, save and load");
+    println!("  mcts [POSITION] [--size N] [--connect K] [--playouts N | --time-ms MS] [--share]");
+    println!("       [--seed N] [--database PATH]");
+    println!("                      Monte Carlo tree search, on any board size");
+    println!("  calibrate [POSITION] [--samples N] [--depth D] [--weights SPEC] [--seed N]");
+    println!("       [--database PATH]");
+    println!("                      measure the evaluator's agreement with the database");
+    println!("  tournament PLAYER PLAYER [--games N] [--seed N] [--output DIR] [--database PATH]");
+    println!("                      play two engines against each other and estimate Elo");
+    println!("  replay FILE [--print] [--no-eval] [--engine database|search] [--database PATH]");
+    println!("                      step through a game record with evaluations");
+    println!("  annotate FILE|MOVES [--output FILE] [--engine database|search] [--database PATH]");
+    println!("                      mark inaccuracies and blunders in a game");
+    println!("  engine [--database PATH]");
+    println!("                      answer the line protocol on stdin for GUIs");
+    println!("  serve [--database PATH] [--port N]");
+    println!("                      answer HTTP/JSON queries on localhost");
+}
+
 fn main() {
-    generate();
+    let args: Vec<String> = std::env::args().collect();
+    match args.get(1).map(|arg| arg.as_str()) {
+        None | Some("generate") => generate(),
+        Some("solve") => solver(&args),
+        Some("verify") => {
+            let path = args
+                .get(2)
+                .map_or(database::DEFAULT_PATH, |arg| arg.as_str());
+            if !verify::verify(path) {
+                std::process::exit(1);
+            }
+        }
+        Some("search") => negamax::search(&args),
+        Some("prove") => pns::prove(&args),
+        Some("mcts") => mcts::mcts(&args),
+        Some("calibrate") => eval::calibrate(&args),
+        Some("strategy") => strategy::strategy(&args),
+        Some("stats") => stats::stats(
+            flag_value(&args, "--database").unwrap_or(database::DEFAULT_PATH),
+            flag_value(&args, "--json"),
+        ),
+        Some("perft") => perft::perft_command(&args),
+        Some("convert") => convert::convert(&args),
+        Some("svg") => svg::svg(&args),
+        Some("pv") => pv::pv(&args),
+        Some("heatmap") => heatmap::heatmap(&args),
+        Some("play") => play::play(&args),
+        Some("versus") => play::versus(&args),
+        Some("replay") => record::replay(&args),
+        Some("annotate") => annotate::annotate(&args),
+        Some("engine") => protocol::engine(&args),
+        Some("serve") => serve::serve(&args),
+        Some("tournament") => tournament::tournament(&args),
+        Some(_) => print_usage(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_path, write_database};
+
+    #[test]
+    fn stats_count_the_positions_of_each_depth() {
+        let variant = Variant::new(3, 3).unwrap();
+        let pegs = notation::parse_moves("5 8 8 3 6 2 3 4 7 7 9 9", 3).unwrap();
+        let root = notation::play_moves(&variant, &pegs).unwrap();
+        let path = temp_path("stats.bin");
+        write_database(&variant, root, &path);
+        let database = Database::open(&path).unwrap();
+
+        // Tally the fixture by hand: finished games are stored with no move
+        let mut positions = [0u64; 28];
+        let mut wins = [0u64; 28];
+        let mut draws = [0u64; 28];
+        for record in database.records().unwrap() {
+            let record = record.unwrap();
+            let position = variant::Position::from_game(&number_to_board(record.state));
+            let pieces = position.moves as usize;
+            positions[pieces] += 1;
+            if record.stored_move == -1 {
+                if record.winner == 0 {
+                    draws[pieces] += 1;
+                } else {
+                    wins[pieces] += 1;
+                }
+            }
+        }
+
+        let stats = stats::collect(&database, |_, _| {}).unwrap();
+        std::fs::remove_file(path).unwrap();
+        assert_eq!(stats.positions, database.len());
+        for depth in &stats.depths {
+            let pieces = depth.pieces;
+            assert_eq!(depth.positions, positions[pieces], "{} pieces", pieces);
+            assert_eq!(
+                depth.player_one_wins + depth.player_two_wins,
+                wins[pieces],
+                "{} pieces",
+                pieces
+            );
+            assert_eq!(depth.full_board_draws, draws[pieces], "{} pieces", pieces);
+            assert_eq!(
+                depth.to_move_wins + depth.to_move_draws + depth.to_move_losses,
+                depth.positions
+            );
+        }
+        assert!((0..12).all(|pieces| stats.depths[pieces].positions == 0));
+        assert_eq!(stats.depths[12].positions, 1);
+        assert_eq!(
+            stats.depths[12].moves,
+            (0..9).filter(|&peg| variant.can_play(&root, peg)).count() as u64
+        );
+
+        // Every won position holds at least one line, and at most one of each kind
+        let won: u64 = wins.iter().sum();
+        let endings = &stats.endings;
+        let lines = endings.axis + endings.face_diagonal + endings.space_diagonal;
+        assert!(won > 0);
+        assert!((won..=3 * won).contains(&lines));
+    }
 }
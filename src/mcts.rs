@@ -0,0 +1,503 @@
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, Instant};
+
+use crate::database::Database;
+use crate::player::{self, Player, Rng};
+use crate::variant::{Position, Variant};
+use crate::{
+    get_all_next_states, is_full, is_over, notation, number_to_board, switch_player, Game,
+};
+
+const DEFAULT_PLAYOUTS: u32 = 1000;
+/// sqrt(2), the usual UCT constant for rewards between 0 and 1
+const EXPLORATION: f64 = std::f64::consts::SQRT_2;
+
+/// What the tree search needs from a game.
+pub trait State: Clone + Eq + Hash {
+    /// Each legal peg with the state it leads to.
+    fn children(&self) -> Vec<(usize, Self)>;
+
+    /// 1 or 2 for the player who completed a line, 0 for a full board, None
+    /// while the game goes on.
+    fn result(&self) -> Option<i8>;
+
+    /// The player to move.
+    fn player(&self) -> i8;
+}
+
+impl State for Game {
+    fn children(&self) -> Vec<(usize, Game)> {
+        // get_all_next_states walks the pegs in order and skips the full ones
+        let pegs = player::legal_pegs(self);
+        let player = switch_player(self.player);
+        pegs.into_iter()
+            .zip(get_all_next_states(self.board.clone(), self.player))
+            .map(|(peg, board)| (peg, Game { board, player }))
+            .collect()
+    }
+
+    fn result(&self) -> Option<i8> {
+        if is_over(self) {
+            Some(switch_player(self.player))
+        } else if is_full(self) {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    fn player(&self) -> i8 {
+        self.player
+    }
+}
+
+/// A position on any board size, for games `Game` cannot hold.
+#[derive(Clone, Copy)]
+pub struct VariantState<'a> {
+    pub variant: &'a Variant,
+    pub position: Position,
+}
+
+impl PartialEq for VariantState<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.position == other.position
+    }
+}
+
+impl Eq for VariantState<'_> {}
+
+impl Hash for VariantState<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.position.hash(state);
+    }
+}
+
+impl State for VariantState<'_> {
+    fn children(&self) -> Vec<(usize, Self)> {
+        (0..self.variant.pegs())
+            .filter_map(|peg| {
+                let mut position = self.position;
+                self.variant.play(&mut position, peg).map(|_| {
+                    (
+                        peg,
+                        VariantState {
+                            variant: self.variant,
+                            position,
+                        },
+                    )
+                })
+            })
+            .collect()
+    }
+
+    fn result(&self) -> Option<i8> {
+        match self.variant.winner(&self.position) {
+            Some(winner) => Some(winner),
+            None if self.variant.is_full(&self.position) => Some(0),
+            None => None,
+        }
+    }
+
+    fn player(&self) -> i8 {
+        self.position.player
+    }
+}
+
+#[derive(Clone, Copy)]
+pub enum Budget {
+    Playouts(u32),
+    Time(Duration),
+}
+
+#[derive(Clone, Copy)]
+pub struct Config {
+    pub budget: Budget,
+    /// Reuses the node of a position reached by different move orders
+    pub share: bool,
+}
+
+impl Config {
+    /// `mcts`, `mcts:PLAYOUTS` or `mcts:MSms`, any of them followed by `:shared`.
+    pub fn parse(spec: &str) -> Result<Config, String> {
+        let mut config = Config {
+            budget: Budget::Playouts(DEFAULT_PLAYOUTS),
+            share: false,
+        };
+        let mut parts = spec.split(':');
+        if parts.next() != Some("mcts") {
+            return Err(format!("{} is not an MCTS player", spec));
+        }
+        for part in parts {
+            config.budget = if part == "shared" {
+                config.share = true;
+                continue;
+            } else if let Some(ms) = part.strip_suffix("ms") {
+                Budget::Time(Duration::from_millis(
+                    ms.parse().map_err(|_| format!("Bad time: {}", part))?,
+                ))
+            } else {
+                Budget::Playouts(
+                    part.parse()
+                        .map_err(|_| format!("Bad playouts: {}", part))?,
+                )
+            };
+        }
+        config.checked()
+    }
+
+    fn checked(self) -> Result<Config, String> {
+        if let Budget::Playouts(0) = self.budget {
+            return Err("MCTS needs at least one playout".to_string());
+        }
+        Ok(self)
+    }
+
+    fn describe(&self) -> String {
+        let budget = match self.budget {
+            Budget::Playouts(playouts) => format!("{} playouts", playouts),
+            Budget::Time(time) => format!("{} ms", time.as_millis()),
+        };
+        if self.share {
+            format!("{}, shared", budget)
+        } else {
+            budget
+        }
+    }
+}
+
+struct Node<S> {
+    state: S,
+    result: Option<i8>,
+    children: Vec<(usize, usize)>,
+    /// Moves not yet added to the tree, with their states
+    untried: Vec<(usize, S)>,
+    visits: u32,
+    /// Summed over the playouts, for the player who moved into this node
+    reward: f64,
+}
+
+/// How the search spread its playouts over the moves from the root.
+pub struct Report {
+    pub best_peg: Option<usize>,
+    /// `(peg, visits, mean reward for the side to move)` for each tried move
+    pub pegs: Vec<(usize, u32, f64)>,
+    pub playouts: u32,
+    pub nodes: usize,
+}
+
+/// UCT with random playouts.
+pub struct Mcts {
+    config: Config,
+    rng: Rng,
+}
+
+impl Mcts {
+    pub fn new(config: Config, seed: u64) -> Mcts {
+        Mcts {
+            config,
+            rng: Rng::new(seed),
+        }
+    }
+
+    fn add_node<S: State>(nodes: &mut Vec<Node<S>>, state: S) -> usize {
+        let result = state.result();
+        let untried = if result.is_none() {
+            state.children()
+        } else {
+            Vec::new()
+        };
+        nodes.push(Node {
+            state,
+            result,
+            children: Vec::new(),
+            untried,
+            visits: 0,
+            reward: 0.0,
+        });
+        nodes.len() - 1
+    }
+
+    fn select<S>(nodes: &[Node<S>], parent: usize) -> usize {
+        let log_visits = (nodes[parent].visits.max(1) as f64).ln();
+        let uct = |child: usize| {
+            let node = &nodes[child];
+            if node.visits == 0 {
+                return f64::INFINITY;
+            }
+            let visits = node.visits as f64;
+            node.reward / visits + EXPLORATION * (log_visits / visits).sqrt()
+        };
+        nodes[parent]
+            .children
+            .iter()
+            .map(|&(_, child)| child)
+            .max_by(|&a, &b| uct(a).total_cmp(&uct(b)))
+            .unwrap()
+    }
+
+    /// Plays random moves to the end of the game and returns the result.
+    fn rollout<S: State>(&mut self, state: &S) -> i8 {
+        let mut state = state.clone();
+        loop {
+            if let Some(result) = state.result() {
+                return result;
+            }
+            let mut children = state.children();
+            let pick = self.rng.below(children.len());
+            state = children.swap_remove(pick).1;
+        }
+    }
+
+    pub fn search<S: State>(&mut self, root: &S) -> Report {
+        let started = Instant::now();
+        let mut nodes: Vec<Node<S>> = Vec::new();
+        let mut table: HashMap<S, usize> = HashMap::new();
+        Mcts::add_node(&mut nodes, root.clone());
+        let mut playouts = 0;
+        loop {
+            let done = match self.config.budget {
+                Budget::Playouts(limit) => playouts >= limit,
+                Budget::Time(limit) => playouts > 0 && started.elapsed() >= limit,
+            };
+            if done || nodes[0].result.is_some() {
+                break;
+            }
+
+            let mut path = vec![0];
+            let mut current = 0;
+            while nodes[current].result.is_none() {
+                if !nodes[current].untried.is_empty() {
+                    let pick = self.rng.below(nodes[current].untried.len());
+                    let (peg, state) = nodes[current].untried.swap_remove(pick);
+                    let child = match table.get(&state) {
+                        Some(&child) => child,
+                        None => {
+                            let child = Mcts::add_node(&mut nodes, state.clone());
+                            if self.config.share {
+                                table.insert(state, child);
+                            }
+                            child
+                        }
+                    };
+                    nodes[current].children.push((peg, child));
+                    path.push(child);
+                    current = child;
+                    break;
+                }
+                current = Mcts::select(&nodes, current);
+                path.push(current);
+            }
+
+            let result = match nodes[current].result {
+                Some(result) => result,
+                None => {
+                    let state = nodes[current].state.clone();
+                    self.rollout(&state)
+                }
+            };
+            for &index in &path {
+                let node = &mut nodes[index];
+                let mover = switch_player(node.state.player());
+                node.visits += 1;
+                node.reward += match result {
+                    0 => 0.5,
+                    winner if winner == mover => 1.0,
+                    _ => 0.0,
+                };
+            }
+            playouts += 1;
+        }
+
+        let mut pegs: Vec<(usize, u32, f64)> = nodes[0]
+            .children
+            .iter()
+            .map(|&(peg, child)| {
+                let node = &nodes[child];
+                (peg, node.visits, node.reward / node.visits.max(1) as f64)
+            })
+            .collect();
+        pegs.sort_by_key(|&(peg, _, _)| peg);
+        let best_peg = pegs
+            .iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then(a.2.total_cmp(&b.2)))
+            .map(|&(peg, _, _)| peg);
+        Report {
+            best_peg,
+            pegs,
+            playouts,
+            nodes: nodes.len(),
+        }
+    }
+}
+
+pub struct MctsPlayer {
+    mcts: Mcts,
+}
+
+impl MctsPlayer {
+    pub fn new(config: Config, seed: u64) -> MctsPlayer {
+        MctsPlayer {
+            mcts: Mcts::new(config, seed),
+        }
+    }
+}
+
+impl Player for MctsPlayer {
+    fn name(&self) -> String {
+        format!("MCTS ({})", self.mcts.config.describe())
+    }
+
+    fn choose(&mut self, g: &Game) -> Result<usize, String> {
+        self.mcts
+            .search(g)
+            .best_peg
+            .ok_or("MCTS found no move".to_string())
+    }
+}
+
+/// The winner under perfect play after `peg`, from the database.
+fn database_result(database: &Database, g: &Game, peg: usize) -> Result<i8, String> {
+    let (_, next) = g
+        .children()
+        .into_iter()
+        .find(|&(child_peg, _)| child_peg == peg)
+        .ok_or("The move is not legal")?;
+    if let Some(result) = next.result() {
+        return Ok(result);
+    }
+    let state_num = crate::game_to_number(next);
+    match database.lookup(state_num) {
+        Ok(Some(record)) => Ok(record.winner),
+        Ok(None) => Err(format!("State {} is not in the database", state_num)),
+        Err(error) => Err(format!("Could not read the database: {}", error)),
+    }
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let variant = crate::variant_flag(args)?;
+    let position = notation::position_arg(args, &variant)?;
+    let mut config = Config {
+        budget: Budget::Playouts(DEFAULT_PLAYOUTS),
+        share: crate::has_switch(args, "--share"),
+    };
+    if let Some(playouts) = crate::flag_value(args, "--playouts") {
+        config.budget = Budget::Playouts(
+            playouts
+                .parse()
+                .map_err(|_| format!("Bad playouts: {}", playouts))?,
+        );
+    }
+    if let Some(ms) = crate::flag_value(args, "--time-ms") {
+        config.budget = Budget::Time(Duration::from_millis(
+            ms.parse().map_err(|_| format!("Bad time: {}", ms))?,
+        ));
+    }
+    let mut mcts = Mcts::new(config.checked()?, player::seed(args)?);
+
+    println!("Variant: {}", variant);
+    let started = Instant::now();
+    let report = if variant.is_standard() {
+        mcts.search(&number_to_board(position.state_number()))
+    } else {
+        mcts.search(&VariantState {
+            variant: &variant,
+            position,
+        })
+    };
+    let elapsed = started.elapsed();
+    if report.pegs.is_empty() {
+        return Err("The game is over".to_string());
+    }
+    println!("Peg        Playouts  Score");
+    for &(peg, visits, score) in &report.pegs {
+        println!(
+            "{:<10} {:>8}  {:.3}",
+            notation::describe_peg(peg, variant.size),
+            visits,
+            score
+        );
+    }
+    let best = report.best_peg.unwrap();
+    println!("Best move: {}", notation::describe_peg(best, variant.size));
+    println!(
+        "Playouts: {}, Nodes: {}, Time: {:.2?}",
+        report.playouts, report.nodes, elapsed
+    );
+
+    if let Some(path) = crate::flag_value(args, "--database") {
+        if !variant.is_standard() {
+            return Err("The database only covers 3x3x3 connect 3".to_string());
+        }
+        let database =
+            Database::open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+        let g = number_to_board(position.state_number());
+        let perfect = player::database_move(&database, &variant, &g)?;
+        let best_result = database_result(&database, &g, perfect)?;
+        let chosen_result = database_result(&database, &g, best)?;
+        let describe = |winner: i8| match winner {
+            0 => "a draw".to_string(),
+            w if w == g.player => "a win".to_string(),
+            _ => "a loss".to_string(),
+        };
+        println!(
+            "Database: {} keeps {}, the MCTS move leads to {}{}",
+            notation::describe_peg(perfect, 3),
+            describe(best_result),
+            describe(chosen_result),
+            if chosen_result == best_result {
+                ""
+            } else {
+                " (MISTAKE)"
+            }
+        );
+    }
+    Ok(())
+}
+
+/// `mcts [POSITION] [--size N] [--connect K] [--playouts N | --time-ms MS] [--share]`
+/// `[--seed N] [--database PATH]`
+pub fn mcts(args: &[String]) {
+    if let Err(error) = run(args) {
+        println!("{}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(moves: &str, share: bool) -> Report {
+        let config = Config {
+            budget: Budget::Playouts(2000),
+            share,
+        };
+        Mcts::new(config, 1).search(&notation::parse_game(moves).unwrap())
+    }
+
+    #[test]
+    fn takes_a_win_and_blocks_a_loss() {
+        // Player 1 holds pegs 1 and 2 on the bottom row, so peg 3 wins for them
+        assert_eq!(search("1 5 2 9", false).best_peg, Some(2));
+        assert_eq!(search("1 5 2", false).best_peg, Some(2));
+    }
+
+    #[test]
+    fn sharing_nodes_builds_a_smaller_tree() {
+        // "5," is the centre opening, a lone "5" would be state number 5
+        let shared = search("5,", true);
+        let unshared = search("5,", false);
+        assert_eq!(shared.playouts, unshared.playouts);
+        assert!(shared.nodes < unshared.nodes);
+    }
+
+    #[test]
+    fn zero_playouts_are_refused() {
+        assert!(Config::parse("mcts:0").is_err());
+        let args = ["--playouts".to_string(), "0".to_string()];
+        assert_eq!(
+            run(&args),
+            Err("MCTS needs at least one playout".to_string())
+        );
+    }
+}
@@ -0,0 +1,400 @@
+use std::time::Instant;
+
+use crate::database::Database;
+use crate::eval::{self, Evaluator};
+use crate::notation;
+use crate::variant::{Position, Variant};
+
+/// Score for a win on the move that fills the board's first cell. Every extra move
+/// before the win costs a point, so faster wins and slower losses score higher.
+pub const WIN: i32 = 10_000;
+
+/// Any score past this is a forced result rather than a guess from the horizon.
+pub fn is_proven(score: i32) -> bool {
+    score.abs() > WIN - 100
+}
+
+const EXACT: u8 = 1;
+const LOWER: u8 = 2;
+const UPPER: u8 = 3;
+
+#[derive(Clone, Copy, Default)]
+struct Entry {
+    pieces: [u64; 2],
+    score: i16,
+    depth: u8,
+    bound: u8,
+    best_peg: u8,
+}
+
+/// Fixed size hash table of earlier results, so memory use is set up front.
+pub struct TranspositionTable {
+    entries: Vec<Entry>,
+    mask: usize,
+}
+
+impl TranspositionTable {
+    pub fn new(megabytes: usize) -> TranspositionTable {
+        let wanted = (megabytes << 20) / std::mem::size_of::<Entry>();
+        let mut len = 1;
+        while len * 2 <= wanted {
+            len *= 2;
+        }
+        TranspositionTable {
+            entries: vec![Entry::default(); len],
+            mask: len - 1,
+        }
+    }
+
+    fn index(&self, position: &Position) -> usize {
+        let key = position.pieces[0] ^ position.pieces[1].rotate_left(32).wrapping_mul(3);
+        let hash = key.wrapping_mul(0x9E37_79B9_7F4A_7C15);
+        ((hash ^ (hash >> 29)) as usize) & self.mask
+    }
+
+    fn probe(&self, position: &Position) -> Option<Entry> {
+        let entry = self.entries[self.index(position)];
+        if entry.bound != 0 && entry.pieces == position.pieces {
+            Some(entry)
+        } else {
+            None
+        }
+    }
+
+    fn store(&mut self, position: &Position, entry: Entry) {
+        let index = self.index(position);
+        let old = &self.entries[index];
+        if old.bound == 0 || old.pieces != entry.pieces || entry.depth >= old.depth {
+            self.entries[index] = entry;
+        }
+    }
+}
+
+pub struct SearchResult {
+    /// From the side to move's point of view, see `WIN`.
+    pub score: i32,
+    pub best_peg: Option<usize>,
+    pub depth: u32,
+    pub nodes: u64,
+    /// Whether the search reached every game end, making the score exact.
+    pub complete: bool,
+}
+
+impl SearchResult {
+    /// The database's winner convention: 1 or 2, 0 for a draw, None if unproven.
+    pub fn winner(&self, position: &Position) -> Option<i8> {
+        if !self.complete {
+            None
+        } else if self.score > 0 {
+            Some(position.player)
+        } else if self.score < 0 {
+            Some(if position.player == 1 { 2 } else { 1 })
+        } else {
+            Some(0)
+        }
+    }
+
+    /// Moves left in the game under best play when the result is proven.
+    pub fn moves_to_end(&self, position: &Position) -> Option<u32> {
+        if is_proven(self.score) {
+            Some((WIN - self.score.abs()) as u32 - position.moves as u32)
+        } else {
+            None
+        }
+    }
+}
+
+/// Alpha-beta negamax with iterative deepening and a transposition table. Works
+/// on any `Variant` directly, without the solved database.
+pub struct Searcher<'a> {
+    variant: &'a Variant,
+    table: TranspositionTable,
+    nodes: u64,
+    /// Scores the positions at the depth limit, which otherwise count as draws
+    evaluator: Option<Evaluator>,
+}
+
+impl<'a> Searcher<'a> {
+    pub fn new(variant: &'a Variant, table_megabytes: usize) -> Searcher<'a> {
+        Searcher::with_table(variant, TranspositionTable::new(table_megabytes))
+    }
+
+    /// Picks up the results in `table`, which must come from `into_table` on a
+    /// searcher for the same variant.
+    pub fn with_table(variant: &'a Variant, table: TranspositionTable) -> Searcher<'a> {
+        Searcher {
+            variant,
+            table,
+            nodes: 0,
+            evaluator: None,
+        }
+    }
+
+    pub fn into_table(self) -> TranspositionTable {
+        self.table
+    }
+
+    pub fn with_evaluator(mut self, evaluator: Evaluator) -> Searcher<'a> {
+        self.evaluator = Some(evaluator);
+        self
+    }
+
+    fn ordered_pegs(&self, position: &Position, first: Option<usize>) -> Vec<usize> {
+        let mut pegs: Vec<usize> = Vec::with_capacity(self.variant.pegs());
+        if let Some(first) = first {
+            pegs.push(first);
+        }
+        for &peg in self.variant.peg_order() {
+            if Some(peg) != first && self.variant.can_play(position, peg) {
+                pegs.push(peg);
+            }
+        }
+        pegs
+    }
+
+    /// Scores one move for the side playing it, searching below it if needed.
+    fn score_move(
+        &mut self,
+        position: &Position,
+        peg: usize,
+        depth: u32,
+        alpha: i32,
+        beta: i32,
+    ) -> i32 {
+        let mut child = *position;
+        let won = self.variant.play(&mut child, peg).unwrap();
+        if won {
+            WIN - child.moves as i32
+        } else if self.variant.is_full(&child) {
+            0
+        } else {
+            -self.negamax(&child, depth - 1, -beta, -alpha)
+        }
+    }
+
+    fn negamax(&mut self, position: &Position, depth: u32, mut alpha: i32, mut beta: i32) -> i32 {
+        self.nodes += 1;
+        if depth == 0 {
+            return self
+                .evaluator
+                .map_or(0, |evaluator| evaluator.evaluate(self.variant, position));
+        }
+        let alpha_start = alpha;
+        let mut first: Option<usize> = None;
+        if let Some(entry) = self.table.probe(position) {
+            first = Some(entry.best_peg as usize);
+            let score = entry.score as i32;
+            let usable = entry.depth as u32 >= depth
+                || (is_proven(score)
+                    && (entry.bound == EXACT || (entry.bound == LOWER) == (score > 0)));
+            if usable {
+                match entry.bound {
+                    EXACT => return score,
+                    LOWER => alpha = alpha.max(score),
+                    _ => beta = beta.min(score),
+                }
+                if alpha >= beta {
+                    return score;
+                }
+            }
+        }
+
+        let mut best_score = -WIN;
+        let mut best_peg = 0;
+        for peg in self.ordered_pegs(position, first) {
+            let score = self.score_move(position, peg, depth, alpha, beta);
+            if score > best_score {
+                best_score = score;
+                best_peg = peg;
+            }
+            alpha = alpha.max(score);
+            if alpha >= beta {
+                break;
+            }
+        }
+
+        let bound = if best_score <= alpha_start {
+            UPPER
+        } else if best_score >= beta {
+            LOWER
+        } else {
+            EXACT
+        };
+        self.table.store(
+            position,
+            Entry {
+                pieces: position.pieces,
+                score: best_score as i16,
+                depth: depth.min(255) as u8,
+                bound,
+                best_peg: best_peg as u8,
+            },
+        );
+        best_score
+    }
+
+    /// Deepens one move at a time until the result is forced or `max_depth` moves
+    /// are searched. Without a limit the search runs to the end of the game and the
+    /// result is exact.
+    pub fn solve(&mut self, position: &Position, max_depth: Option<u32>) -> SearchResult {
+        self.nodes = 0;
+        if let Some(winner) = self.variant.winner(position) {
+            let score = WIN - position.moves as i32;
+            return SearchResult {
+                score: if winner == position.player {
+                    score
+                } else {
+                    -score
+                },
+                best_peg: None,
+                depth: 0,
+                nodes: 0,
+                complete: true,
+            };
+        }
+        if self.variant.is_full(position) {
+            return SearchResult {
+                score: 0,
+                best_peg: None,
+                depth: 0,
+                nodes: 0,
+                complete: true,
+            };
+        }
+
+        let remaining = (self.variant.cells() - position.moves as usize) as u32;
+        let limit = max_depth.map_or(remaining, |depth| depth.min(remaining));
+        let mut result = SearchResult {
+            score: 0,
+            best_peg: None,
+            depth: 0,
+            nodes: 0,
+            complete: false,
+        };
+        for depth in 1..=limit {
+            let mut alpha = -WIN;
+            let mut best_peg = None;
+            for peg in self.ordered_pegs(position, result.best_peg) {
+                let score = self.score_move(position, peg, depth, alpha, WIN);
+                if best_peg.is_none() || score > alpha {
+                    alpha = score;
+                    best_peg = Some(peg);
+                }
+            }
+            result = SearchResult {
+                score: alpha,
+                best_peg,
+                depth,
+                nodes: self.nodes,
+                complete: is_proven(alpha) || depth == remaining,
+            };
+            if is_proven(alpha) {
+                break;
+            }
+        }
+        result
+    }
+}
+
+/// `search [state] [--size N] [--connect K] [--depth D] [--eval] [--weights SPEC]`
+/// `[--table-mb M] [--database PATH]`
+pub fn search(args: &[String]) {
+    let variant = match crate::variant_flag(args) {
+        Ok(variant) => variant,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let position = match notation::position_arg(args, &variant) {
+        Ok(position) => position,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let max_depth = crate::flag_value(args, "--depth").and_then(|depth| depth.parse().ok());
+    let table_megabytes = crate::flag_value(args, "--table-mb")
+        .and_then(|megabytes| megabytes.parse().ok())
+        .unwrap_or(256);
+
+    let mut searcher = Searcher::new(&variant, table_megabytes);
+    if crate::has_switch(args, "--eval") || crate::flag_value(args, "--weights").is_some() {
+        match eval::evaluator_flag(args) {
+            Ok(evaluator) => searcher = searcher.with_evaluator(evaluator),
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        }
+    }
+
+    println!("Variant: {}", variant);
+    let started = Instant::now();
+    let result = searcher.solve(&position, max_depth);
+    if let Some(peg) = result.best_peg {
+        println!("Best move: {}", notation::describe_peg(peg, variant.size));
+    }
+    match (result.winner(&position), result.moves_to_end(&position)) {
+        (Some(0), _) => println!("Result: draw"),
+        (Some(winner), Some(moves)) => {
+            println!("Result: player {} wins in {} moves", winner, moves)
+        }
+        _ => println!("Result: unproven, score {}", result.score),
+    }
+    println!(
+        "Depth: {}, Nodes: {}, Time: {:.2?}",
+        result.depth,
+        result.nodes,
+        started.elapsed()
+    );
+
+    if let Some(path) = crate::flag_value(args, "--database") {
+        let state_num = position.state_number();
+        match Database::open(path).map(|database| database.lookup(state_num)) {
+            Ok(Ok(Some(record))) => {
+                let agrees = result.winner(&position) == Some(record.winner);
+                println!(
+                    "Database winner: {}{}",
+                    record.winner,
+                    if agrees { "" } else { " (DISAGREES)" }
+                );
+            }
+            Ok(Ok(None)) => println!("State {} is not in {}", state_num, path),
+            Ok(Err(error)) | Err(error) => println!("Could not read {}: {}", path, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_player_wins_the_standard_game_in_nine() {
+        let variant = Variant::new(3, 3).unwrap();
+        let start = variant.start();
+        let result = Searcher::new(&variant, 16).solve(&start, None);
+        assert_eq!(result.winner(&start), Some(1));
+        assert_eq!(result.moves_to_end(&start), Some(9));
+    }
+
+    #[test]
+    fn first_player_wins_two_by_two_in_three() {
+        let variant = Variant::new(2, 2).unwrap();
+        let start = variant.start();
+        let result = Searcher::new(&variant, 1).solve(&start, None);
+        assert_eq!(result.winner(&start), Some(1));
+        assert_eq!(result.moves_to_end(&start), Some(3));
+    }
+
+    #[test]
+    fn depth_limit_leaves_the_result_unproven() {
+        let variant = Variant::new(3, 3).unwrap();
+        let start = variant.start();
+        let result = Searcher::new(&variant, 16).solve(&start, Some(3));
+        assert!(!result.complete);
+        assert_eq!(result.winner(&start), None);
+        assert_eq!(result.depth, 3);
+    }
+}
@@ -0,0 +1,180 @@
+use crate::convert;
+use crate::variant::{Position, Variant};
+use crate::{number_to_board, Game, STATES};
+
+pub fn format_peg(peg: usize) -> String {
+    (peg + 1).to_string()
+}
+
+pub fn format_square(peg: usize, size: usize) -> String {
+    format!("{}{}", (b'a' + (peg % size) as u8) as char, peg / size + 1)
+}
+
+/// A peg in both notations, for output people read.
+pub fn describe_peg(peg: usize, size: usize) -> String {
+    format!("{} ({})", format_peg(peg), format_square(peg, size))
+}
+
+/// A peg is written either as its number counted from 1 along the rows, so 1 to 9
+/// on the standard board, or as a square like b2: the letter is the column
+/// (Right) and the digit the row (Down). Peg 5 and b2 are both the centre.
+/// Returns the peg counted from 0.
+pub fn parse_peg(text: &str, size: usize) -> Result<usize, String> {
+    let error = || format!("Bad move: {}", text);
+    let text = text.trim().to_ascii_lowercase();
+    let mut chars = text.chars();
+    let peg = match chars.next() {
+        Some(column @ 'a'..='z') => {
+            let column = column as usize - 'a' as usize;
+            let row: usize = chars.as_str().parse().map_err(|_| error())?;
+            if column >= size || row == 0 || row > size {
+                return Err(error());
+            }
+            (row - 1) * size + column
+        }
+        _ => {
+            let number: usize = text.parse().map_err(|_| error())?;
+            if number == 0 || number > size * size {
+                return Err(error());
+            }
+            number - 1
+        }
+    };
+    Ok(peg)
+}
+
+/// A move sequence from the empty board, pegs separated by spaces or commas
+/// like "5 1 9 5".
+pub fn parse_moves(text: &str, size: usize) -> Result<Vec<usize>, String> {
+    text.split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|word| !word.is_empty())
+        .map(|word| parse_peg(word, size))
+        .collect()
+}
+
+pub fn format_moves(pegs: &[usize]) -> String {
+    let words: Vec<String> = pegs.iter().map(|&peg| format_peg(peg)).collect();
+    words.join(" ")
+}
+
+/// Plays the pegs from the empty board, refusing full pegs and moves after the
+/// game has ended.
+pub fn play_moves(variant: &Variant, pegs: &[usize]) -> Result<Position, String> {
+    let mut position = variant.start();
+    let mut over = false;
+    for (index, &peg) in pegs.iter().enumerate() {
+        if over || variant.is_full(&position) {
+            return Err(format!("Move {} comes after the game has ended", index + 1));
+        }
+        match variant.play(&mut position, peg) {
+            Some(won) => over = won,
+            None => {
+                return Err(format!(
+                    "Move {}: peg {} is full",
+                    index + 1,
+                    format_peg(peg)
+                ))
+            }
+        }
+    }
+    Ok(position)
+}
+
+/// A lone number is a state number; anything with a space, comma or letter is a
+/// move sequence. A single move is written as a square (b2) or with a comma (5,).
+pub fn is_move_sequence(text: &str) -> bool {
+    text.contains(|c: char| c.is_whitespace() || c == ',' || c.is_ascii_alphabetic())
+}
+
+pub fn parse_position(variant: &Variant, text: &str) -> Result<Position, String> {
+    if is_move_sequence(text) {
+        return play_moves(variant, &parse_moves(text, variant.size)?);
+    }
+    parse_state(variant, text)
+}
+
+/// A state number, refused unless its board can come up in a game: no floating
+/// pieces, piece counts that fit the side to move and no play after a line.
+pub fn parse_state(variant: &Variant, text: &str) -> Result<Position, String> {
+    if !variant.is_standard() {
+        return Err("State numbers only describe 3x3x3 connect 3 positions".to_string());
+    }
+    let state_num = match text.trim().parse::<u64>() {
+        Ok(state_num) if state_num < STATES => state_num,
+        _ => return Err(format!("Bad state number: {}", text)),
+    };
+    let g = number_to_board(state_num);
+    convert::moves_to(&g)
+        .map_err(|error| format!("State {} cannot come up in a game: {}", state_num, error))?;
+    Ok(Position::from_game(&g))
+}
+
+/// A state number for a game still in play, where proofs and strategies start.
+pub fn parse_unfinished_state(variant: &Variant, text: &str) -> Result<Position, String> {
+    let position = parse_state(variant, text)?;
+    if variant.winner(&position).is_some() || variant.is_full(&position) {
+        return Err(format!("State {} is a finished game", text.trim()));
+    }
+    Ok(position)
+}
+
+pub fn parse_game(text: &str) -> Result<Game, String> {
+    let variant = Variant::new(3, 3).unwrap();
+    parse_position(&variant, text).map(|position| number_to_board(position.state_number()))
+}
+
+/// The position named by the command's first positional argument, or the empty
+/// board. Move sequences also print the state number they reach.
+pub fn position_arg(args: &[String], variant: &Variant) -> Result<Position, String> {
+    let text = match crate::positional(args, 0) {
+        Some(text) => text,
+        None => return Ok(variant.start()),
+    };
+    let position = parse_position(variant, text)?;
+    if is_move_sequence(text) && variant.is_standard() {
+        println!("State: {}", position.state_number());
+    }
+    Ok(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn state_numbers_must_come_up_in_a_game() {
+        let variant = Variant::new(3, 3).unwrap();
+        assert_eq!(parse_position(&variant, "0"), Ok(variant.start()));
+        let centre = play_moves(&variant, &[4]).unwrap();
+        assert_eq!(
+            parse_position(&variant, &centre.state_number().to_string()),
+            Ok(centre)
+        );
+        // A piece floating above an empty cell
+        assert!(parse_position(&variant, "19683").is_err());
+        // Player 2 has the only piece
+        assert!(parse_position(&variant, "2").is_err());
+        assert!(parse_position(&variant, "7625597484987").is_err());
+    }
+
+    #[test]
+    fn play_after_a_line_is_refused() {
+        let variant = Variant::new(3, 3).unwrap();
+        // Player 1 completes 1 4 7 and player 2 has moved again
+        let pegs = parse_moves("1 2 4 5 7 9", 3).unwrap();
+        assert!(play_moves(&variant, &pegs).is_err());
+        let mut position = play_moves(&variant, &pegs[..5]).unwrap();
+        variant.play(&mut position, 8);
+        let state = position.state_number().to_string();
+        assert!(parse_position(&variant, &state).is_err());
+    }
+
+    #[test]
+    fn bad_state_numbers_are_named() {
+        let variant = Variant::new(3, 3).unwrap();
+        assert_eq!(
+            parse_state(&variant, "abc"),
+            Err("Bad state number: abc".to_string())
+        );
+    }
+}
@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+
+use crate::{board_to_number, get_all_next_states, is_full, is_over, number_to_board};
+
+/// Known-good counts, checked by `perft --check`.
+pub const FIXTURES: &str = "fixtures/perft.txt";
+
+/// Counts for one ply of the game tree.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PerftCounts {
+    pub depth: usize,
+    /// Move sequences of this length, so transpositions count separately
+    pub sequences: u128,
+    /// Distinct positions reached by those sequences
+    pub positions: u64,
+    /// Sequences that end the game on their last move
+    pub finished: u128,
+}
+
+/// Walks the game tree one ply at a time with `get_all_next_states` and `is_over`,
+/// carrying the number of move sequences that reach each position.
+pub fn perft(max_depth: usize) -> Vec<PerftCounts> {
+    let mut counts = Vec::new();
+    let mut layer: HashMap<u64, u128> = HashMap::new();
+    layer.insert(0, 1);
+    for depth in 0..=max_depth {
+        let mut next_layer: HashMap<u64, u128> = HashMap::new();
+        let mut sequences: u128 = 0;
+        let mut finished: u128 = 0;
+        for (&state_num, &paths) in &layer {
+            sequences += paths;
+            let g = number_to_board(state_num);
+            if is_over(&g) || is_full(&g) {
+                finished += paths;
+                continue;
+            }
+            if depth == max_depth {
+                continue;
+            }
+            for next_state in get_all_next_states(g.board, g.player) {
+                *next_layer.entry(board_to_number(&next_state)).or_insert(0) += paths;
+            }
+        }
+        counts.push(PerftCounts {
+            depth,
+            sequences,
+            positions: layer.len() as u64,
+            finished,
+        });
+        if next_layer.is_empty() {
+            break;
+        }
+        layer = next_layer;
+    }
+    counts
+}
+
+/// Fixture lines are `depth sequences positions finished`, plus an optional
+/// `total positions` line that only a walk to the end of the game can check.
+fn read_fixtures(path: &str) -> Result<(Vec<PerftCounts>, Option<u64>), String> {
+    let file = File::open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+    let mut fixtures = Vec::new();
+    let mut total = None;
+    for line in BufReader::new(file).lines().map_while(Result::ok) {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        match words[..] {
+            [] => {}
+            [first, ..] if first.starts_with('#') => {}
+            ["total", positions] => {
+                total = Some(
+                    positions
+                        .parse()
+                        .map_err(|_| format!("Bad line: {}", line))?,
+                )
+            }
+            [depth, sequences, positions, finished] => {
+                let parse = || -> Option<PerftCounts> {
+                    Some(PerftCounts {
+                        depth: depth.parse().ok()?,
+                        sequences: sequences.parse().ok()?,
+                        positions: positions.parse().ok()?,
+                        finished: finished.parse().ok()?,
+                    })
+                };
+                fixtures.push(parse().ok_or(format!("Bad line: {}", line))?);
+            }
+            _ => return Err(format!("Bad line: {}", line)),
+        }
+    }
+    Ok((fixtures, total))
+}
+
+fn print_counts(counts: &PerftCounts) {
+    println!(
+        "{:>5} {:>30} {:>12} {:>30}",
+        counts.depth, counts.sequences, counts.positions, counts.finished
+    );
+}
+
+/// `perft [--depth D]` or `perft --check [FILE] [--depth D]`
+pub fn perft_command(args: &[String]) {
+    let max_depth: usize = crate::flag_value(args, "--depth")
+        .and_then(|depth| depth.parse().ok())
+        .unwrap_or(27);
+    if !crate::has_switch(args, "--check") {
+        println!(
+            "{:>5} {:>30} {:>12} {:>30}",
+            "Depth", "Sequences", "Positions", "Finished"
+        );
+        let counts = perft(max_depth);
+        for depth in &counts {
+            print_counts(depth);
+        }
+        println!(
+            "Total positions: {}",
+            counts.iter().map(|depth| depth.positions).sum::<u64>()
+        );
+        return;
+    }
+
+    let path = crate::positional(args, 0).unwrap_or(FIXTURES);
+    let (fixtures, total) = match read_fixtures(path) {
+        Ok(fixtures) => fixtures,
+        Err(error) => {
+            println!("{}", error);
+            std::process::exit(1);
+        }
+    };
+    let deepest = fixtures
+        .iter()
+        .map(|fixture| fixture.depth)
+        .max()
+        .unwrap_or(0);
+    let counts = perft(if total.is_some() {
+        max_depth
+    } else {
+        max_depth.min(deepest)
+    });
+    let mut failures = 0;
+    for fixture in fixtures.iter().filter(|fixture| fixture.depth <= max_depth) {
+        match counts.get(fixture.depth) {
+            Some(found) if found == fixture => print_counts(found),
+            found => {
+                failures += 1;
+                println!("Depth {} expected:", fixture.depth);
+                print_counts(fixture);
+                match found {
+                    Some(found) => {
+                        println!("found:");
+                        print_counts(found);
+                    }
+                    None => println!("found: nothing"),
+                }
+            }
+        }
+    }
+    let reached_end = counts
+        .last()
+        .is_some_and(|last| last.finished == last.sequences);
+    match total {
+        Some(total) if reached_end => {
+            let found: u64 = counts.iter().map(|depth| depth.positions).sum();
+            if found == total {
+                println!("Total positions: {}", found);
+            } else {
+                failures += 1;
+                println!("Total positions expected {}, found {}", total, found);
+            }
+        }
+        Some(_) => println!("Total not checked, --depth stops before the end of the game"),
+        None => {}
+    }
+    if failures > 0 {
+        println!("{} mismatches", failures);
+        std::process::exit(1);
+    }
+    println!("All counts match");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn shallow_counts_match_the_fixtures() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/fixtures/perft.txt");
+        let (fixtures, total) = read_fixtures(path).unwrap();
+        assert!(total.is_some());
+        let counts = perft(7);
+        let shallow: Vec<PerftCounts> = fixtures
+            .into_iter()
+            .filter(|fixture| fixture.depth <= 7)
+            .collect();
+        assert_eq!(shallow.len(), 8);
+        assert_eq!(counts, shallow);
+    }
+}
@@ -0,0 +1,484 @@
+use std::io::Write;
+use std::sync::Arc;
+
+use crate::database::{self, Database};
+use crate::negamax::Searcher;
+use crate::notation;
+use crate::player::{self, Player};
+use crate::record::GameRecord;
+use crate::render::{self, Highlights, Layout, Style};
+use crate::variant::{Position, Variant};
+use crate::{
+    game_to_number, is_full, is_over, make_new_board, place_new_piece, switch_player, Game,
+};
+
+/// Where the computer's moves come from.
+pub enum Engine<'a> {
+    /// Shared so `serve` can give every request its own engine
+    Database(Arc<Database>),
+    Search(Searcher<'a>),
+}
+
+impl Engine<'_> {
+    pub fn choose(&mut self, variant: &Variant, g: &Game) -> Result<usize, String> {
+        match self {
+            Engine::Database(database) => player::database_move(database, variant, g),
+            Engine::Search(searcher) => searcher
+                .solve(&Position::from_game(g), None)
+                .best_peg
+                .ok_or("The search found no move".to_string()),
+        }
+    }
+
+    /// The winner under perfect play from `g`, 0 for a draw.
+    pub fn evaluate(&mut self, g: &Game) -> Result<i8, String> {
+        match self {
+            Engine::Database(database) => match database.lookup(game_to_number(g.clone())) {
+                Ok(Some(record)) => Ok(record.winner),
+                Ok(None) => Err("This state is not in the database".to_string()),
+                Err(error) => Err(format!("Could not read the database: {}", error)),
+            },
+            Engine::Search(searcher) => {
+                let position = Position::from_game(g);
+                searcher
+                    .solve(&position, None)
+                    .winner(&position)
+                    .ok_or("The search did not finish".to_string())
+            }
+        }
+    }
+
+    /// The winner under perfect play after each legal move from `g`.
+    pub fn peg_results(&mut self, g: &Game) -> Result<Vec<(usize, i8)>, String> {
+        let mut results = Vec::new();
+        for peg in 0..9 {
+            let mut next = g.clone();
+            if place_new_piece(&mut next.board, peg / 3, peg % 3, g.player).is_none() {
+                continue;
+            }
+            next.player = switch_player(g.player);
+            let winner = if is_over(&next) {
+                g.player
+            } else if is_full(&next) {
+                0
+            } else {
+                self.evaluate(&next)?
+            };
+            results.push((peg, winner));
+        }
+        Ok(results)
+    }
+}
+
+pub fn open_engine<'a>(args: &[String], variant: &'a Variant) -> Result<Engine<'a>, String> {
+    match crate::flag_value(args, "--engine").unwrap_or("database") {
+        "database" => {
+            let path = crate::flag_value(args, "--database").unwrap_or(database::DEFAULT_PATH);
+            Database::open(path)
+                .map(|database| Engine::Database(Arc::new(database)))
+                .map_err(|error| {
+                    format!(
+                        "Could not open {}: {}\nUse --engine search to play without the database",
+                        path, error
+                    )
+                })
+        }
+        "search" => Ok(Engine::Search(Searcher::new(variant, 64))),
+        other => Err(format!("Unknown engine: {}", other)),
+    }
+}
+
+pub fn prompt(message: &str) -> Option<String> {
+    print!("{}", message);
+    std::io::stdout().flush().unwrap();
+    let mut input = String::new();
+    match std::io::stdin().read_line(&mut input) {
+        Ok(0) | Err(_) => None,
+        Ok(_) => Some(input.trim().to_string()),
+    }
+}
+
+fn print_board(g: &Game, style: &Style, highlights: &Highlights) {
+    render::print(g, style, highlights);
+    println!("Pegs: 1 2 3 / 4 5 6 / 7 8 9 or a1 b1 c1 / a2 b2 c2 / a3 b3 c3");
+}
+
+/// `--player1` and `--player2`, or a human on `--side` against `--engine`.
+fn seat_players<'a>(
+    args: &[String],
+    variant: &'a Variant,
+) -> Result<[Box<dyn Player + 'a>; 2], String> {
+    let seed = player::seed(args)?;
+    let (names, flags) = match (
+        crate::flag_value(args, "--player1"),
+        crate::flag_value(args, "--player2"),
+    ) {
+        (Some(one), Some(two)) => ([one, two], ["--player1", "--player2"]),
+        (None, None) => {
+            let engine = crate::flag_value(args, "--engine").unwrap_or("database");
+            let human: i8 = match crate::flag_value(args, "--side") {
+                Some(side) => side.parse().unwrap_or(0),
+                None => prompt("Play as player 1 or 2? ")
+                    .and_then(|side| side.parse().ok())
+                    .unwrap_or(0),
+            };
+            match human {
+                1 => (["human", engine], ["--engine"; 2]),
+                2 => ([engine, "human"], ["--engine"; 2]),
+                _ => return Err("Pick side 1 or 2".to_string()),
+            }
+        }
+        _ => return Err("Give both --player1 and --player2".to_string()),
+    };
+    Ok([
+        player::open_player(names[0], flags[0], args, variant, seed)?,
+        player::open_player(names[1], flags[1], args, variant, seed.wrapping_add(1))?,
+    ])
+}
+
+/// `play [--side 1|2] [--engine NAME] [--player1 NAME --player2 NAME] [--seed N]`
+/// `[--database PATH] [--record FILE] [--board LAYOUT] [--colour] [--unicode]`
+///
+/// Players are human, random, greedy, database or search.
+pub fn play(args: &[String]) {
+    let variant = Variant::new(3, 3).unwrap();
+    let style = match Style::from_args(args, Layout::Layers) {
+        Ok(style) => style,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let mut players = match seat_players(args, &variant) {
+        Ok(players) => players,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let one_human = players[0].is_human() != players[1].is_human();
+
+    let mut g = Game {
+        board: make_new_board(),
+        player: 1,
+    };
+    let mut moves: Vec<usize> = Vec::new();
+    loop {
+        print_board(&g, &style, &Highlights::for_game(&g, moves.last().copied()));
+        if is_over(&g) {
+            let winner = switch_player(g.player);
+            let player = &players[(winner - 1) as usize];
+            if one_human && player.is_human() {
+                println!("You win!");
+            } else if one_human {
+                println!("{} wins", player.name());
+            } else {
+                println!("Player {} ({}) wins", winner, player.name());
+            }
+            break;
+        }
+        if is_full(&g) {
+            println!("Draw, the board is full");
+            break;
+        }
+
+        let player = &mut players[(g.player - 1) as usize];
+        let peg = match player.choose(&g) {
+            Ok(peg) => peg,
+            Err(error) => {
+                println!("{}", error);
+                break;
+            }
+        };
+        if place_new_piece(&mut g.board, peg / 3, peg % 3, g.player).is_none() {
+            println!("{} played on full peg {}", player.name(), peg + 1);
+            break;
+        }
+        if !player.is_human() {
+            println!("{} plays {}", player.name(), notation::describe_peg(peg, 3));
+        }
+        g.player = switch_player(g.player);
+        moves.push(peg);
+    }
+
+    if let Some(path) = crate::flag_value(args, "--record") {
+        let mut record = GameRecord::new(&moves);
+        record.set_header("Player1", &players[0].name());
+        record.set_header("Player2", &players[1].name());
+        match record.write(path) {
+            Ok(()) => println!("Game record written to {}", path),
+            Err(error) => println!("Could not write {}: {}", path, error),
+        }
+    }
+}
+
+/// A game between two people kept as its list of pegs, so undo and redo only
+/// have to replay the moves.
+struct Session {
+    moves: Vec<usize>,
+    undone: Vec<usize>,
+}
+
+impl Session {
+    fn new() -> Session {
+        Session {
+            moves: Vec::new(),
+            undone: Vec::new(),
+        }
+    }
+
+    fn game(&self) -> Game {
+        let mut g = Game {
+            board: make_new_board(),
+            player: 1,
+        };
+        for &peg in &self.moves {
+            place_new_piece(&mut g.board, peg / 3, peg % 3, g.player);
+            g.player = switch_player(g.player);
+        }
+        g
+    }
+
+    fn play(&mut self, peg: usize) -> Result<(), String> {
+        let mut g = self.game();
+        if is_over(&g) || is_full(&g) {
+            return Err("The game is over, undo or start a new one".to_string());
+        }
+        if place_new_piece(&mut g.board, peg / 3, peg % 3, g.player).is_none() {
+            return Err(format!("Peg {} is full", notation::format_peg(peg)));
+        }
+        self.moves.push(peg);
+        self.undone.clear();
+        Ok(())
+    }
+
+    fn undo(&mut self) -> Result<(), String> {
+        let peg = self.moves.pop().ok_or("Nothing to undo")?;
+        self.undone.push(peg);
+        Ok(())
+    }
+
+    fn redo(&mut self) -> Result<(), String> {
+        let peg = self.undone.pop().ok_or("Nothing to redo")?;
+        self.moves.push(peg);
+        Ok(())
+    }
+
+    fn move_list(&self) -> String {
+        notation::format_moves(&self.moves)
+    }
+
+    fn save(&self, path: &str) -> std::io::Result<()> {
+        GameRecord::new(&self.moves).write(path)
+    }
+
+    /// Reads a game record or a plain move list, checking every move as it is
+    /// replayed.
+    fn load(path: &str) -> Result<Session, String> {
+        let record = GameRecord::read(path)?;
+        if !record.variant()?.is_standard() {
+            return Err("Only 3x3x3 connect 3 games can be loaded".to_string());
+        }
+        let mut session = Session::new();
+        for peg in record.pegs() {
+            session.play(peg)?;
+        }
+        Ok(session)
+    }
+}
+
+const VERSUS_HELP: &str =
+    "Commands: a peg 1-9 or a1-c3, u (undo), r (redo), moves, save FILE, load FILE, new, q";
+
+/// `versus [--hint] [--engine database|search] [--database PATH] [--board LAYOUT] [--colour] [--unicode] [FILE]`
+pub fn versus(args: &[String]) {
+    let variant = Variant::new(3, 3).unwrap();
+    let style = match Style::from_args(args, Layout::Layers) {
+        Ok(style) => style,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let mut engine = None;
+    if crate::has_switch(args, "--hint") {
+        match open_engine(args, &variant) {
+            Ok(opened) => engine = Some(opened),
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        }
+    }
+    let mut session = match crate::positional(args, 0) {
+        Some(path) => match Session::load(path) {
+            Ok(session) => session,
+            Err(error) => {
+                println!("{}", error);
+                return;
+            }
+        },
+        None => Session::new(),
+    };
+    println!("{}", VERSUS_HELP);
+
+    loop {
+        let g = session.game();
+        let mut highlights = Highlights::for_game(&g, session.moves.last().copied());
+        let finished = is_over(&g) || is_full(&g);
+        let hint = match engine.as_mut() {
+            Some(engine) if !finished => {
+                highlights.best_move = engine.choose(&variant, &g).ok();
+                Some(engine.evaluate(&g))
+            }
+            _ => None,
+        };
+        print_board(&g, &style, &highlights);
+        if is_over(&g) {
+            println!("Player {} wins", switch_player(g.player));
+        } else if is_full(&g) {
+            println!("Draw, the board is full");
+        }
+        match hint {
+            Some(Ok(0)) => println!("Hint: draw with best play, + marks the best move"),
+            Some(Ok(winner)) => println!(
+                "Hint: player {} wins with best play, + marks the best move",
+                winner
+            ),
+            Some(Err(error)) => println!("Hint unavailable: {}", error),
+            None => {}
+        }
+
+        let message = if finished {
+            "> ".to_string()
+        } else {
+            format!("Player {} to move> ", g.player)
+        };
+        let input = match prompt(&message) {
+            Some(input) => input,
+            None => return,
+        };
+        let (command, argument) = match input.split_once(' ') {
+            Some((command, argument)) => (command, argument.trim()),
+            None => (input.as_str(), ""),
+        };
+        match command {
+            "q" | "quit" => return,
+            "u" | "undo" => {
+                if let Err(error) = session.undo() {
+                    println!("{}", error);
+                }
+            }
+            "r" | "redo" => {
+                if let Err(error) = session.redo() {
+                    println!("{}", error);
+                }
+            }
+            "moves" => println!("Moves: {}", session.move_list()),
+            "new" => session = Session::new(),
+            "save" => match session.save(argument) {
+                Ok(()) => println!("Saved to {}", argument),
+                Err(error) => println!("Could not save {}: {}", argument, error),
+            },
+            "load" => match Session::load(argument) {
+                Ok(loaded) => session = loaded,
+                Err(error) => println!("{}", error),
+            },
+            _ => match notation::parse_peg(&input, 3) {
+                Ok(peg) => {
+                    if let Err(error) = session.play(peg) {
+                        println!("{}", error);
+                    }
+                }
+                Err(_) => println!("{}", VERSUS_HELP),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_path;
+
+    #[test]
+    fn undo_and_redo_step_through_the_moves() {
+        let mut session = Session::new();
+        for peg in [4, 0, 8] {
+            session.play(peg).unwrap();
+        }
+        session.undo().unwrap();
+        session.undo().unwrap();
+        assert_eq!(session.moves, vec![4]);
+        session.redo().unwrap();
+        assert_eq!(session.moves, vec![4, 0]);
+        assert_eq!(game_to_number(session.game()), {
+            let mut replayed = Session::new();
+            replayed.play(4).unwrap();
+            replayed.play(0).unwrap();
+            game_to_number(replayed.game())
+        });
+        session.redo().unwrap();
+        assert_eq!(session.moves, vec![4, 0, 8]);
+    }
+
+    #[test]
+    fn a_new_move_after_undo_drops_the_redo() {
+        let mut session = Session::new();
+        for peg in [4, 0, 8] {
+            session.play(peg).unwrap();
+        }
+        session.undo().unwrap();
+        session.undo().unwrap();
+        session.play(2).unwrap();
+        assert_eq!(session.moves, vec![4, 2]);
+        assert!(session.undone.is_empty());
+        assert_eq!(session.redo().unwrap_err(), "Nothing to redo");
+    }
+
+    #[test]
+    fn undo_at_the_start_and_redo_at_the_end_are_refused() {
+        let mut session = Session::new();
+        assert_eq!(session.undo().unwrap_err(), "Nothing to undo");
+        session.play(4).unwrap();
+        assert_eq!(session.redo().unwrap_err(), "Nothing to redo");
+        session.undo().unwrap();
+        assert_eq!(session.undo().unwrap_err(), "Nothing to undo");
+        assert!(session.moves.is_empty());
+        assert_eq!(session.undone, vec![4]);
+    }
+
+    #[test]
+    fn a_saved_session_loads_back() {
+        let mut session = Session::new();
+        for peg in [4, 0, 8, 4, 2] {
+            session.play(peg).unwrap();
+        }
+        let path = temp_path("versus.txt");
+        session.save(&path).unwrap();
+        let loaded = Session::load(&path);
+        std::fs::remove_file(path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.moves, session.moves);
+        assert_eq!(loaded.move_list(), session.move_list());
+        assert_eq!(
+            game_to_number(loaded.game()),
+            game_to_number(session.game())
+        );
+    }
+
+    #[test]
+    fn a_corrupt_file_is_refused() {
+        let path = temp_path("corrupt.txt");
+        for contents in [
+            "1. 5 5 2. 5 5 3. 5",
+            "1. 5 x 2. 9",
+            "[Result \"1-0\"]\n\n1. 5 1 *",
+        ] {
+            std::fs::write(&path, contents).unwrap();
+            assert!(Session::load(&path).is_err(), "{}", contents);
+        }
+        std::fs::remove_file(&path).unwrap();
+        assert!(Session::load(&path).is_err());
+    }
+}
@@ -0,0 +1,352 @@
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::database::{self, Database};
+use crate::mcts::{Config, MctsPlayer};
+use crate::negamax::Searcher;
+use crate::notation;
+use crate::play::prompt;
+use crate::variant::{Position, Variant};
+use crate::{board_to_number, is_over, place_new_piece, Game};
+
+/// Picks moves for one side of a game.
+pub trait Player {
+    /// Shown in messages and written to game records.
+    fn name(&self) -> String;
+
+    /// A legal peg, counted from 0, for the side to move in `g`. Only asked while
+    /// the game is still going; an error ends the game.
+    fn choose(&mut self, g: &Game) -> Result<usize, String>;
+
+    fn is_human(&self) -> bool {
+        false
+    }
+}
+
+pub const PLAYER_NAMES: &str =
+    "human, random, greedy, database, search or mcts[:PLAYOUTS|:MSms][:shared]";
+
+/// xorshift64*, enough to vary games without pulling in a crate.
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        // Zero is the one state xorshift never leaves, and one seed mixes to it
+        let state = match seed ^ 0x9e37_79b9_7f4a_7c15 {
+            0 => 0x9e37_79b9_7f4a_7c15,
+            state => state,
+        };
+        Rng { state }
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+
+    /// Uniform enough below `n` for game play.
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+}
+
+/// `--seed N`, or the clock when it is left out.
+pub fn seed(args: &[String]) -> Result<u64, String> {
+    match crate::flag_value(args, "--seed") {
+        Some(seed) => seed.parse().map_err(|_| format!("Bad seed: {}", seed)),
+        None => Ok(SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos() as u64)),
+    }
+}
+
+pub fn legal_pegs(g: &Game) -> Vec<usize> {
+    (0..9)
+        .filter(|&peg| g.board.data[peg / 3][peg % 3][2] == 0)
+        .collect()
+}
+
+/// Whether `player` completes a line by dropping on `peg`.
+fn wins_with(g: &Game, peg: usize, player: i8) -> bool {
+    let mut next = g.clone();
+    place_new_piece(&mut next.board, peg / 3, peg % 3, player).is_some() && is_over(&next)
+}
+
+pub struct Human;
+
+impl Player for Human {
+    fn name(&self) -> String {
+        "Human".to_string()
+    }
+
+    fn is_human(&self) -> bool {
+        true
+    }
+
+    fn choose(&mut self, g: &Game) -> Result<usize, String> {
+        loop {
+            let input = match prompt("Your move (peg 1-9 or a1-c3, q to quit): ") {
+                Some(input) if input != "q" => input,
+                _ => return Err("Game abandoned".to_string()),
+            };
+            match notation::parse_peg(&input, 3) {
+                Ok(peg) if legal_pegs(g).contains(&peg) => return Ok(peg),
+                Ok(peg) => println!("Peg {} is full", notation::format_peg(peg)),
+                Err(error) => println!("{}", error),
+            }
+        }
+    }
+}
+
+pub struct Random {
+    rng: Rng,
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Random {
+        Random {
+            rng: Rng::new(seed),
+        }
+    }
+}
+
+impl Player for Random {
+    fn name(&self) -> String {
+        "Random".to_string()
+    }
+
+    fn choose(&mut self, g: &Game) -> Result<usize, String> {
+        let pegs = legal_pegs(g);
+        if pegs.is_empty() {
+            return Err("There are no legal moves".to_string());
+        }
+        Ok(pegs[self.rng.below(pegs.len())])
+    }
+}
+
+/// Wins when it can, otherwise blocks the opponent's immediate win, otherwise
+/// plays at random.
+pub struct Greedy {
+    random: Random,
+}
+
+impl Greedy {
+    pub fn new(seed: u64) -> Greedy {
+        Greedy {
+            random: Random::new(seed),
+        }
+    }
+}
+
+impl Player for Greedy {
+    fn name(&self) -> String {
+        "Greedy".to_string()
+    }
+
+    fn choose(&mut self, g: &Game) -> Result<usize, String> {
+        let pegs = legal_pegs(g);
+        let opponent = crate::switch_player(g.player);
+        if let Some(&peg) = pegs.iter().find(|&&peg| wins_with(g, peg, g.player)) {
+            return Ok(peg);
+        }
+        if let Some(&peg) = pegs.iter().find(|&&peg| wins_with(g, peg, opponent)) {
+            return Ok(peg);
+        }
+        self.random.choose(g)
+    }
+}
+
+/// Plays perfectly from the solved database.
+pub struct Perfect {
+    database: Arc<Database>,
+    variant: Variant,
+}
+
+impl Perfect {
+    pub fn new(database: Arc<Database>) -> Perfect {
+        Perfect {
+            database,
+            variant: Variant::new(3, 3).unwrap(),
+        }
+    }
+}
+
+impl Player for Perfect {
+    fn name(&self) -> String {
+        "Engine (database)".to_string()
+    }
+
+    fn choose(&mut self, g: &Game) -> Result<usize, String> {
+        database_move(&self.database, &self.variant, g)
+    }
+}
+
+/// Looks up every next state and takes a win, then a draw, then anything. Ties go
+/// to the pegs with the most lines through them.
+pub fn database_move(database: &Database, variant: &Variant, g: &Game) -> Result<usize, String> {
+    let mut best: Option<(usize, i8)> = None;
+    for &peg in variant.peg_order() {
+        let mut next = g.clone();
+        if place_new_piece(&mut next.board, peg / 3, peg % 3, g.player).is_none() {
+            continue;
+        }
+        let rank = if is_over(&next) {
+            3
+        } else {
+            let next_number = board_to_number(&next.board);
+            match database.lookup(next_number) {
+                Ok(Some(record)) if record.winner == g.player => 2,
+                Ok(Some(record)) if record.winner == 0 => 1,
+                Ok(Some(_)) => 0,
+                Ok(None) => return Err(format!("State {} is not in the database", next_number)),
+                Err(error) => return Err(format!("Could not read the database: {}", error)),
+            }
+        };
+        if best.is_none_or(|(_, most)| rank > most) {
+            best = Some((peg, rank));
+        }
+    }
+    best.map(|(peg, _)| peg)
+        .ok_or("There are no legal moves".to_string())
+}
+
+/// Plays perfectly with the alpha-beta search, taking the fastest win and the
+/// slowest loss.
+pub struct Search<'a> {
+    searcher: Searcher<'a>,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(variant: &'a Variant) -> Search<'a> {
+        Search {
+            searcher: Searcher::new(variant, 64),
+        }
+    }
+}
+
+impl Player for Search<'_> {
+    fn name(&self) -> String {
+        "Engine (search)".to_string()
+    }
+
+    fn choose(&mut self, g: &Game) -> Result<usize, String> {
+        self.searcher
+            .solve(&Position::from_game(g), None)
+            .best_peg
+            .ok_or("The search found no move".to_string())
+    }
+}
+
+pub fn open_database(args: &[String]) -> Result<Arc<Database>, String> {
+    let path = crate::flag_value(args, "--database").unwrap_or(database::DEFAULT_PATH);
+    Database::open(path)
+        .map(Arc::new)
+        .map_err(|error| format!("Could not open {}: {}", path, error))
+}
+
+/// One of `PLAYER_NAMES`, given with `flag`. The database player opens
+/// `--database`.
+pub fn open_player<'a>(
+    name: &str,
+    flag: &str,
+    args: &[String],
+    variant: &'a Variant,
+    seed: u64,
+) -> Result<Box<dyn Player + 'a>, String> {
+    let database = match name {
+        "database" => Some(open_database(args).map_err(|error| {
+            format!(
+                "{}\nUse {} search to play without the database",
+                error, flag
+            )
+        })?),
+        _ => None,
+    };
+    new_player(name, database, variant, seed)
+}
+
+/// One of `PLAYER_NAMES`, with the database player sharing an open database.
+pub fn new_player<'a>(
+    name: &str,
+    database: Option<Arc<Database>>,
+    variant: &'a Variant,
+    seed: u64,
+) -> Result<Box<dyn Player + 'a>, String> {
+    match name {
+        "human" => Ok(Box::new(Human)),
+        "random" => Ok(Box::new(Random::new(seed))),
+        "greedy" => Ok(Box::new(Greedy::new(seed))),
+        "database" => database
+            .map(|database| Box::new(Perfect::new(database)) as Box<dyn Player>)
+            .ok_or("The database player needs a database".to_string()),
+        "search" => Ok(Box::new(Search::new(variant))),
+        mcts if mcts.starts_with("mcts") => {
+            Ok(Box::new(MctsPlayer::new(Config::parse(mcts)?, seed)))
+        }
+        other => Err(format!("Unknown player {}, use {}", other, PLAYER_NAMES)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_path, write_database};
+    use crate::{game_to_number, number_to_board};
+
+    fn game(moves: &str) -> Game {
+        notation::parse_game(moves).unwrap()
+    }
+
+    #[test]
+    fn every_seed_gives_a_moving_generator() {
+        // --seed 11400714819323198485, which mixes to the zero state
+        let mut rng = Rng::new(0x9e37_79b9_7f4a_7c15);
+        let draws: Vec<u64> = (0..4).map(|_| rng.next()).collect();
+        assert!(draws.iter().all(|&draw| draw != 0));
+        assert_ne!(draws[0], draws[1]);
+    }
+
+    #[test]
+    fn greedy_takes_a_win_and_blocks_a_loss() {
+        // Player 1 holds pegs 1 and 2 on the bottom row, so peg 3 ends the game
+        for seed in 0..20 {
+            let mut greedy = Greedy::new(seed);
+            assert_eq!(greedy.choose(&game("1 5 2 9")), Ok(2));
+            assert_eq!(greedy.choose(&game("1 5 2")), Ok(2));
+        }
+    }
+
+    #[test]
+    fn perfect_keeps_the_database_result() {
+        let variant = Variant::new(3, 3).unwrap();
+        let root = game("5 8 8 3 6 2 3 4 7 7 9 9");
+        let path = temp_path("perfect.bin");
+        write_database(&variant, Position::from_game(&root), &path);
+        let database = Arc::new(Database::open(&path).unwrap());
+        let mut perfect = Perfect::new(Arc::clone(&database));
+
+        let mut g = root;
+        while !is_over(&g) && !crate::is_full(&g) {
+            let winner = database
+                .lookup(game_to_number(g.clone()))
+                .unwrap()
+                .unwrap()
+                .winner;
+            let peg = perfect.choose(&g).unwrap();
+            let mut next = g.clone();
+            place_new_piece(&mut next.board, peg / 3, peg % 3, g.player).unwrap();
+            let next = number_to_board(board_to_number(&next.board));
+            let after = database
+                .lookup(game_to_number(next.clone()))
+                .unwrap()
+                .unwrap();
+            assert_eq!(after.winner, winner);
+            g = next;
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,521 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use crate::notation::{self, format_peg};
+use crate::variant::{Position, Variant};
+
+const INFINITE: u32 = u32::MAX;
+/// Nodes take about 200 bytes each with their child lists, so the default
+/// limit needs up to 2 GB. Proving the empty board takes well under a million.
+const DEFAULT_NODES: usize = 10_000_000;
+
+struct Node {
+    position: Position,
+    parent: Option<usize>,
+    peg: usize,
+    proof: u32,
+    disproof: u32,
+    children: Vec<usize>,
+    expanded: bool,
+}
+
+/// Best-first proof-number search for a forced win by the player to move at the
+/// root. Draws count as failures, so a disproof means the defender can hold at
+/// least a draw. Nodes are kept in an arena and transpositions are not merged.
+pub struct ProofSearch<'a> {
+    variant: &'a Variant,
+    attacker: i8,
+    nodes: Vec<Node>,
+}
+
+impl<'a> ProofSearch<'a> {
+    pub fn new(variant: &'a Variant, root: Position) -> ProofSearch<'a> {
+        ProofSearch {
+            variant,
+            attacker: root.player,
+            nodes: vec![Node {
+                position: root,
+                parent: None,
+                peg: 0,
+                proof: 1,
+                disproof: 1,
+                children: Vec::new(),
+                expanded: false,
+            }],
+        }
+    }
+
+    fn is_or_node(&self, index: usize) -> bool {
+        self.nodes[index].position.player == self.attacker
+    }
+
+    fn expand(&mut self, index: usize) {
+        let position = self.nodes[index].position;
+        let mover_is_attacker = position.player == self.attacker;
+        for peg in 0..self.variant.pegs() {
+            let mut child = position;
+            let won = match self.variant.play(&mut child, peg) {
+                Some(won) => won,
+                None => continue,
+            };
+            let (proof, disproof) = if won && mover_is_attacker {
+                (0, INFINITE)
+            } else if won || self.variant.is_full(&child) {
+                (INFINITE, 0)
+            } else {
+                (1, 1)
+            };
+            let child_index = self.nodes.len();
+            self.nodes.push(Node {
+                position: child,
+                parent: Some(index),
+                peg,
+                proof,
+                disproof,
+                children: Vec::new(),
+                expanded: proof != 1,
+            });
+            self.nodes[index].children.push(child_index);
+        }
+        self.nodes[index].expanded = true;
+    }
+
+    fn update(&mut self, index: usize) {
+        let children = &self.nodes[index].children;
+        let (proof, disproof) = if self.is_or_node(index) {
+            (
+                children.iter().map(|&c| self.nodes[c].proof).min().unwrap(),
+                children
+                    .iter()
+                    .fold(0u32, |sum, &c| sum.saturating_add(self.nodes[c].disproof)),
+            )
+        } else {
+            (
+                children
+                    .iter()
+                    .fold(0u32, |sum, &c| sum.saturating_add(self.nodes[c].proof)),
+                children
+                    .iter()
+                    .map(|&c| self.nodes[c].disproof)
+                    .min()
+                    .unwrap(),
+            )
+        };
+        self.nodes[index].proof = proof;
+        self.nodes[index].disproof = disproof;
+    }
+
+    fn most_proving(&self) -> usize {
+        let mut index = 0;
+        while self.nodes[index].expanded {
+            let children = &self.nodes[index].children;
+            index = if self.is_or_node(index) {
+                *children
+                    .iter()
+                    .min_by_key(|&&c| self.nodes[c].proof)
+                    .unwrap()
+            } else {
+                *children
+                    .iter()
+                    .min_by_key(|&&c| self.nodes[c].disproof)
+                    .unwrap()
+            };
+        }
+        index
+    }
+
+    /// Runs until the root is proved or disproved, or `max_nodes` are allocated.
+    /// Returns Some(true) for a proven win.
+    pub fn run(&mut self, max_nodes: usize) -> Option<bool> {
+        if self.variant.winner(&self.nodes[0].position).is_some()
+            || self.variant.is_full(&self.nodes[0].position)
+        {
+            return Some(false);
+        }
+        while self.nodes[0].proof != 0 && self.nodes[0].disproof != 0 {
+            if self.nodes.len() >= max_nodes {
+                return None;
+            }
+            let mut index = self.most_proving();
+            self.expand(index);
+            self.update(index);
+            while let Some(parent) = self.nodes[index].parent {
+                index = parent;
+                self.update(index);
+            }
+        }
+        Some(self.nodes[0].proof == 0)
+    }
+
+    pub fn nodes(&self) -> usize {
+        self.nodes.len()
+    }
+
+    /// Number of attacker moves in the smallest proof below `index`.
+    fn proof_size(&self, index: usize, sizes: &mut Vec<Option<usize>>) -> usize {
+        if let Some(size) = sizes[index] {
+            return size;
+        }
+        let node = &self.nodes[index];
+        let size = if node.children.is_empty() {
+            0
+        } else if self.is_or_node(index) {
+            1 + node
+                .children
+                .iter()
+                .filter(|&&c| self.nodes[c].proof == 0)
+                .map(|&c| self.proof_size(c, sizes))
+                .min()
+                .unwrap()
+        } else {
+            node.children
+                .iter()
+                .map(|&c| self.proof_size(c, sizes))
+                .sum()
+        };
+        sizes[index] = Some(size);
+        size
+    }
+
+    fn chosen_child(&self, index: usize, sizes: &mut Vec<Option<usize>>) -> usize {
+        *self.nodes[index]
+            .children
+            .iter()
+            .filter(|&&c| self.nodes[c].proof == 0)
+            .min_by_key(|&&c| self.proof_size(c, sizes))
+            .unwrap()
+    }
+
+    fn write_answer(
+        &self,
+        index: usize,
+        depth: usize,
+        prefix: String,
+        sizes: &mut Vec<Option<usize>>,
+        lines: &mut Vec<String>,
+    ) {
+        let chosen = self.chosen_child(index, sizes);
+        let indent = "  ".repeat(depth);
+        if self.nodes[chosen].children.is_empty() {
+            lines.push(format!(
+                "{}{}{} win",
+                indent,
+                prefix,
+                format_peg(self.nodes[chosen].peg)
+            ));
+            return;
+        }
+        lines.push(format!(
+            "{}{}{}",
+            indent,
+            prefix,
+            format_peg(self.nodes[chosen].peg)
+        ));
+        for &reply in &self.nodes[chosen].children {
+            self.write_answer(
+                reply,
+                depth + 1,
+                format!("{} ", format_peg(self.nodes[reply].peg)),
+                sizes,
+                lines,
+            );
+        }
+    }
+
+    /// The proof as text: one line per attacker move, each holding the defender's
+    /// reply and the answer to it, indented under the position it was played in.
+    pub fn proof_lines(&self) -> Vec<String> {
+        let mut sizes = vec![None; self.nodes.len()];
+        let mut lines = Vec::new();
+        self.write_answer(0, 0, String::new(), &mut sizes, &mut lines);
+        lines
+    }
+}
+
+fn header(variant: &Variant, state_num: Option<u64>) -> String {
+    match state_num {
+        Some(state_num) => format!(
+            "size {} connect {} state {}",
+            variant.size, variant.connect, state_num
+        ),
+        None => format!("size {} connect {}", variant.size, variant.connect),
+    }
+}
+
+/// Replays a proof file against the rules. Every attacker move must be legal and
+/// every legal defender reply must be answered until each line ends in a win.
+/// Returns the number of attacker moves in the proof.
+pub fn check_proof(path: &str) -> Result<usize, String> {
+    let file = File::open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+    let lines: Vec<(usize, String)> = BufReader::new(file)
+        .lines()
+        .map_while(Result::ok)
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.starts_with('#'))
+        .collect();
+    let (_, first) = lines.first().ok_or("Empty proof")?;
+    let words: Vec<&str> = first.split_whitespace().collect();
+    let number = |at: usize| -> Result<usize, String> {
+        words
+            .get(at)
+            .and_then(|word| word.parse().ok())
+            .ok_or(format!("Bad header: {}", first))
+    };
+    if words.first() != Some(&"size") || words.get(2) != Some(&"connect") {
+        return Err(format!("Bad header: {}", first));
+    }
+    let variant = Variant::new(number(1)?, number(3)?)?;
+    let root = if words.get(4) == Some(&"state") {
+        let state = words.get(5).ok_or(format!("Bad header: {}", first))?;
+        notation::parse_unfinished_state(&variant, state)?
+    } else {
+        variant.start()
+    };
+
+    let mut checker = ProofChecker {
+        variant: &variant,
+        lines: &lines[1..],
+        next: 0,
+        answers: 0,
+    };
+    checker.check_answer(root, 0, None)?;
+    if checker.next != checker.lines.len() {
+        return Err(format!(
+            "Line {}: not part of the proof",
+            checker.lines[checker.next].0 + 1
+        ));
+    }
+    Ok(checker.answers)
+}
+
+/// A peg as proof files write it, counted from 1.
+fn read_peg(word: &str) -> Option<usize> {
+    word.parse::<usize>().ok()?.checked_sub(1)
+}
+
+struct ProofChecker<'a> {
+    variant: &'a Variant,
+    lines: &'a [(usize, String)],
+    next: usize,
+    answers: usize,
+}
+
+impl ProofChecker<'_> {
+    /// Reads the line answering `reply` (None at the root) in `position`.
+    fn check_answer(
+        &mut self,
+        position: Position,
+        depth: usize,
+        reply: Option<usize>,
+    ) -> Result<(), String> {
+        let (number, line) = self
+            .lines
+            .get(self.next)
+            .ok_or("Proof ends early".to_string())?;
+        let at = |message: &str| format!("Line {}: {}", number + 1, message);
+        self.next += 1;
+        self.answers += 1;
+        if line.len() - line.trim_start().len() != depth * 2 {
+            return Err(at("wrong indentation"));
+        }
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let mut words = words.iter();
+        if let Some(reply) = reply {
+            if words.next().and_then(|word| read_peg(word)) != Some(reply) {
+                return Err(at(&format!("expected the reply {}", format_peg(reply))));
+            }
+        }
+        let peg: usize = words
+            .next()
+            .and_then(|word| read_peg(word))
+            .filter(|&peg| peg < self.variant.pegs())
+            .ok_or(at("missing answer"))?;
+        let claims_win = match words.next() {
+            None => false,
+            Some(&"win") => true,
+            Some(_) => return Err(at("unexpected text")),
+        };
+        let mut after = position;
+        let won = self
+            .variant
+            .play(&mut after, peg)
+            .ok_or(at("answer on a full peg"))?;
+        if won != claims_win {
+            return Err(at(if won {
+                "answer wins but is not marked"
+            } else {
+                "answer does not win"
+            }));
+        }
+        if won {
+            return Ok(());
+        }
+        if self.variant.is_full(&after) {
+            return Err(at("board fills without a win"));
+        }
+        for reply in 0..self.variant.pegs() {
+            let mut next = after;
+            match self.variant.play(&mut next, reply) {
+                None => continue,
+                Some(true) => return Err(at(&format!("the reply {} wins", format_peg(reply)))),
+                Some(false) if self.variant.is_full(&next) => {
+                    return Err(at(&format!("the reply {} draws", format_peg(reply))))
+                }
+                Some(false) => self.check_answer(next, depth + 1, Some(reply))?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// `prove [state] [--size N] [--connect K] [--nodes LIMIT] [--output FILE]`
+/// or `prove --check FILE`
+pub fn prove(args: &[String]) {
+    if let Some(path) = crate::flag_value(args, "--check") {
+        match check_proof(path) {
+            Ok(answers) => println!("Valid proof, {} attacker moves", answers),
+            Err(error) => {
+                println!("Invalid proof: {}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let variant = match crate::variant_flag(args) {
+        Ok(variant) => variant,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let root = match crate::notation::position_arg(args, &variant) {
+        Ok(root) => root,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let state_num = if variant.is_standard() {
+        Some(root.state_number())
+    } else if root.moves > 0 {
+        println!("Proofs for other variants start from the empty board");
+        return;
+    } else {
+        None
+    };
+    let max_nodes = crate::flag_value(args, "--nodes")
+        .and_then(|nodes| nodes.parse().ok())
+        .unwrap_or(DEFAULT_NODES);
+
+    println!("Variant: {}", variant);
+    let mut search = ProofSearch::new(&variant, root);
+    match search.run(max_nodes) {
+        Some(true) => {
+            let lines = search.proof_lines();
+            println!(
+                "Player {} wins, proof has {} attacker moves ({} nodes searched)",
+                root.player,
+                lines.len(),
+                search.nodes()
+            );
+            let path = crate::flag_value(args, "--output").unwrap_or("proof.txt");
+            let mut output = File::create(path).unwrap();
+            writeln!(
+                output,
+                "# Player {} to move wins. Pegs are numbered 1 + Down * {} + Right.",
+                root.player, variant.size
+            )
+            .unwrap();
+            writeln!(
+                output,
+                "# Each line is a defender reply and the answer to it, indented under its position."
+            )
+            .unwrap();
+            writeln!(output, "{}", header(&variant, state_num)).unwrap();
+            for line in lines {
+                writeln!(output, "{}", line).unwrap();
+            }
+            println!("Proof written to {}", path);
+        }
+        Some(false) => println!(
+            "Disproved: player {} cannot force a win ({} nodes searched)",
+            root.player,
+            search.nodes()
+        ),
+        None => println!("Gave up after {} nodes", search.nodes()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_path;
+
+    fn write_proof(name: &str, lines: &[String]) -> String {
+        write_rooted_proof(name, "size 3 connect 3", lines)
+    }
+
+    fn write_rooted_proof(name: &str, header: &str, lines: &[String]) -> String {
+        let path = temp_path(&format!("{}.txt", name));
+        let mut output = File::create(&path).unwrap();
+        writeln!(output, "{}", header).unwrap();
+        for line in lines {
+            writeln!(output, "{}", line).unwrap();
+        }
+        path
+    }
+
+    #[test]
+    fn proves_the_start_and_the_proof_checks() {
+        let variant = Variant::new(3, 3).unwrap();
+        let mut search = ProofSearch::new(&variant, variant.start());
+        assert_eq!(search.run(1_000_000), Some(true));
+        let lines = search.proof_lines();
+        let path = write_proof("proof", &lines);
+        assert_eq!(check_proof(&path), Ok(lines.len()));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn disproves_a_lost_position() {
+        let variant = Variant::new(3, 3).unwrap();
+        let mut after_centre = variant.start();
+        variant.play(&mut after_centre, 4);
+        assert_eq!(
+            ProofSearch::new(&variant, after_centre).run(1_000_000),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn check_rejects_a_changed_answer() {
+        let variant = Variant::new(3, 3).unwrap();
+        let mut search = ProofSearch::new(&variant, variant.start());
+        search.run(1_000_000);
+        let mut lines = search.proof_lines();
+        // The rest of the proof then answers replies in the wrong position
+        lines[0] = if lines[0] == "2" { "3" } else { "2" }.to_string();
+        let path = write_proof("bad-proof", &lines);
+        assert!(check_proof(&path).is_err());
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn check_rejects_roots_no_game_is_in() {
+        let won = notation::parse_game("1 5 2 9 3").unwrap();
+        let roots = [
+            // 3^27, one past the last state number
+            ("7625597484987".to_string(), "Bad state number"),
+            // Two pieces for player 1 and none for player 2
+            ("4".to_string(), "cannot come up in a game"),
+            (crate::game_to_number(won).to_string(), "finished game"),
+        ];
+        for (root, problem) in roots {
+            let header = format!("size 3 connect 3 state {}", root);
+            let path = write_rooted_proof("bad-root", &header, &["5".to_string()]);
+            let error = check_proof(&path).unwrap_err();
+            assert!(error.contains(problem), "{}", error);
+            std::fs::remove_file(path).unwrap();
+        }
+    }
+}
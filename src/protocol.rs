@@ -0,0 +1,338 @@
+use std::collections::hash_map::{Entry, HashMap};
+use std::io::{BufRead, Write};
+use std::sync::Arc;
+
+use crate::database::{self, Database};
+use crate::negamax::{SearchResult, Searcher, TranspositionTable};
+use crate::notation;
+use crate::number_to_board;
+use crate::play::Engine;
+use crate::variant::{Position, Variant};
+
+const TABLE_MEGABYTES: usize = 64;
+/// Depth for `best` and `values` without one on variants the search cannot
+/// solve in time.
+const DEFAULT_DEPTH: u32 = 10;
+
+fn value_word(winner: i8, player: i8) -> &'static str {
+    match winner {
+        0 => "draw",
+        w if w == player => "win",
+        _ => "loss",
+    }
+}
+
+/// The search's value of `position` for `player`: win, draw, loss, or unknown
+/// when a depth limited search did not reach the end, with the moves left in the
+/// game plus `extra_moves` when known.
+fn search_value(
+    result: &SearchResult,
+    position: &Position,
+    player: i8,
+    extra_moves: u32,
+) -> String {
+    let value = match result.winner(position) {
+        Some(winner) => value_word(winner, player),
+        None => "unknown",
+    };
+    match result.moves_to_end(position) {
+        Some(moves) => format!("{}:{}", value, moves + extra_moves),
+        None => value.to_string(),
+    }
+}
+
+struct Session {
+    /// Every variant used so far, keyed by size and line length
+    variants: HashMap<(usize, usize), Variant>,
+    current: (usize, usize),
+    position: Position,
+    /// Only used for 3x3x3 connect 3, other variants are searched
+    engine: Option<Engine<'static>>,
+    /// Made on the first search and kept until the variant changes
+    table: Option<TranspositionTable>,
+}
+
+impl Session {
+    fn new(engine: Option<Engine<'static>>) -> Session {
+        let variant = Variant::new(3, 3).unwrap();
+        Session {
+            current: (variant.size, variant.connect),
+            position: variant.start(),
+            variants: HashMap::from([((variant.size, variant.connect), variant)]),
+            engine,
+            table: None,
+        }
+    }
+
+    fn variant(&self) -> &Variant {
+        &self.variants[&self.current]
+    }
+
+    fn set_variant(&mut self, size: usize, connect: usize) -> Result<(), String> {
+        if let Entry::Vacant(entry) = self.variants.entry((size, connect)) {
+            entry.insert(Variant::new(size, connect)?);
+        }
+        if self.current != (size, connect) {
+            self.current = (size, connect);
+            self.table = None;
+        }
+        self.position = self.variant().start();
+        Ok(())
+    }
+
+    /// Runs `search` with a searcher on the session's table.
+    fn with_searcher<T>(&mut self, search: impl FnOnce(&mut Searcher, &Variant) -> T) -> T {
+        let table = self
+            .table
+            .take()
+            .unwrap_or_else(|| TranspositionTable::new(TABLE_MEGABYTES));
+        let variant = &self.variants[&self.current];
+        let mut searcher = Searcher::with_table(variant, table);
+        let result = search(&mut searcher, variant);
+        self.table = Some(searcher.into_table());
+        result
+    }
+
+    /// Only the standard game is solved in time without a depth.
+    fn depth_or_default(&self, depth: Option<u32>) -> Option<u32> {
+        if self.variant().is_standard() {
+            depth
+        } else {
+            depth.or(Some(DEFAULT_DEPTH))
+        }
+    }
+
+    fn uses_database(&self) -> bool {
+        self.engine.is_some() && self.variant().is_standard()
+    }
+
+    fn is_finished(&self) -> bool {
+        self.variant().winner(&self.position).is_some() || self.variant().is_full(&self.position)
+    }
+
+    fn set_position(&mut self, words: &[&str]) -> Result<String, String> {
+        let position = match words {
+            ["startpos"] => self.variant().start(),
+            ["startpos", "moves", moves @ ..] | ["moves", moves @ ..] => notation::play_moves(
+                self.variant(),
+                &notation::parse_moves(&moves.join(" "), self.variant().size)?,
+            )?,
+            ["state", state] => notation::parse_state(self.variant(), state)?,
+            _ => return Err("usage: position startpos|state N|moves PEG...".to_string()),
+        };
+        self.position = position;
+        Ok(self.describe())
+    }
+
+    fn describe(&self) -> String {
+        let mut reply = format!(
+            "ok player {} ply {}",
+            self.position.player, self.position.moves
+        );
+        if self.variant().is_standard() {
+            reply.push_str(&format!(" state {}", self.position.state_number()));
+        }
+        if let Some(winner) = self.variant().winner(&self.position) {
+            reply.push_str(&format!(" over winner {}", winner));
+        } else if self.variant().is_full(&self.position) {
+            reply.push_str(" over draw");
+        }
+        reply
+    }
+
+    fn best(&mut self, depth: Option<u32>) -> Result<String, String> {
+        if self.is_finished() {
+            return Ok("bestmove none".to_string());
+        }
+        if self.uses_database() {
+            let g = number_to_board(self.position.state_number());
+            let engine = self.engine.as_mut().unwrap();
+            let peg = engine.choose(&self.variants[&self.current], &g)?;
+            let winner = engine.evaluate(&g)?;
+            return Ok(format!(
+                "bestmove {} value {}",
+                notation::format_peg(peg),
+                value_word(winner, self.position.player)
+            ));
+        }
+        let (position, depth) = (self.position, self.depth_or_default(depth));
+        let result = self.with_searcher(|searcher, _| searcher.solve(&position, depth));
+        let peg = result.best_peg.ok_or("the search found no move")?;
+        Ok(format!(
+            "bestmove {} value {}",
+            notation::format_peg(peg),
+            search_value(&result, &self.position, self.position.player, 0)
+        ))
+    }
+
+    /// Each peg's value for the side to move once it is played.
+    fn values(&mut self, depth: Option<u32>) -> Result<String, String> {
+        if self.is_finished() {
+            return Err("the game is over".to_string());
+        }
+        let mut values = vec!["full".to_string(); self.variant().pegs()];
+        if self.uses_database() {
+            let g = number_to_board(self.position.state_number());
+            let engine = self.engine.as_mut().unwrap();
+            for (peg, winner) in engine.peg_results(&g)? {
+                values[peg] = value_word(winner, self.position.player).to_string();
+            }
+        } else {
+            let (position, depth) = (self.position, self.depth_or_default(depth));
+            self.with_searcher(|searcher, variant| {
+                for (peg, value) in values.iter_mut().enumerate() {
+                    let mut child = position;
+                    *value = match variant.play(&mut child, peg) {
+                        None => continue,
+                        Some(true) => "win:1".to_string(),
+                        Some(false) if variant.is_full(&child) => "draw:1".to_string(),
+                        Some(false) => {
+                            search_value(&searcher.solve(&child, depth), &child, position.player, 1)
+                        }
+                    };
+                }
+            });
+        }
+        let values: Vec<String> = values
+            .iter()
+            .enumerate()
+            .map(|(peg, value)| format!("{}={}", notation::format_peg(peg), value))
+            .collect();
+        Ok(format!("values {}", values.join(" ")))
+    }
+
+    fn handle(&mut self, line: &str) -> Result<Option<String>, String> {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let depth = |words: &[&str]| -> Result<Option<u32>, String> {
+            match words {
+                [] => Ok(None),
+                ["depth", depth] => match depth.parse() {
+                    Ok(0) | Err(_) => Err(format!("bad depth {}, use 1 or more", depth)),
+                    Ok(depth) => Ok(Some(depth)),
+                },
+                _ => Err("usage: best|values [depth N]".to_string()),
+            }
+        };
+        let reply = match words[..] {
+            [] => return Ok(None),
+            ["protocol"] => format!(
+                "protocol 1 engine {}",
+                if self.uses_database() {
+                    "database"
+                } else {
+                    "search"
+                }
+            ),
+            ["isready"] => "readyok".to_string(),
+            ["newgame"] => {
+                self.position = self.variant().start();
+                self.describe()
+            }
+            ["variant", size, connect] => {
+                let size = size.parse().map_err(|_| format!("bad size {}", size))?;
+                let connect = connect
+                    .parse()
+                    .map_err(|_| format!("bad line length {}", connect))?;
+                self.set_variant(size, connect)?;
+                format!("ok variant {}", self.variant())
+            }
+            ["position", ref rest @ ..] => self.set_position(rest)?,
+            ["best", ref rest @ ..] => self.best(depth(rest)?)?,
+            ["values", ref rest @ ..] => self.values(depth(rest)?)?,
+            ["quit"] => return Ok(Some("bye".to_string())),
+            [command, ..] => return Err(format!("unknown command {}", command)),
+        };
+        Ok(Some(reply))
+    }
+}
+
+/// Answers each line of `input` on `output` until `quit` or the end of the input.
+fn run(session: &mut Session, input: impl BufRead, mut output: impl Write) {
+    for line in input.lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        let (reply, quit) = match session.handle(&line) {
+            Ok(Some(reply)) => {
+                let quit = reply == "bye";
+                (reply, quit)
+            }
+            Ok(None) => continue,
+            Err(error) => (format!("error {}", error), false),
+        };
+        if writeln!(output, "{}", reply)
+            .and_then(|_| output.flush())
+            .is_err()
+            || quit
+        {
+            break;
+        }
+    }
+}
+
+/// `engine [--database PATH]`
+///
+/// Reads one command per line and answers each with one line. Errors reply
+/// `error <message>` and leave the session as it was. See the README for the
+/// commands.
+pub fn engine(args: &[String]) {
+    let path = crate::flag_value(args, "--database").unwrap_or(database::DEFAULT_PATH);
+    let mut session = Session::new(
+        Database::open(path)
+            .ok()
+            .map(|database| Engine::Database(Arc::new(database))),
+    );
+    run(&mut session, std::io::stdin().lock(), std::io::stdout());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The replies to `input`, one per line, from a session without the database.
+    fn replies(input: &str) -> Vec<String> {
+        let mut output = Vec::new();
+        run(&mut Session::new(None), input.as_bytes(), &mut output);
+        String::from_utf8(output)
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+
+    #[test]
+    fn bad_commands_get_an_error_and_the_loop_goes_on() {
+        let replies = replies(
+            "fly away\n\
+             position state 19683\n\
+             position state abc\n\
+             best depth 0\n\
+             variant 3 4\n\
+             variant 9 3\n\
+             isready\n\
+             position moves 5\n\
+             quit\n\
+             isready\n",
+        );
+        assert_eq!(replies.len(), 9);
+        assert_eq!(replies[0], "error unknown command fly");
+        assert!(replies[1].starts_with("error State 19683 cannot come up in a game"));
+        assert_eq!(replies[2], "error Bad state number: abc");
+        assert_eq!(replies[3], "error bad depth 0, use 1 or more");
+        assert_eq!(replies[4], "error line length must be 2 to 3, not 4");
+        assert_eq!(replies[5], "error board size must be 2 to 4, not 9");
+        assert_eq!(replies[6], "readyok");
+        assert_eq!(replies[7], "ok player 2 ply 1 state 81");
+        assert_eq!(replies[8], "bye");
+    }
+
+    #[test]
+    fn variants_keep_their_own_search() {
+        let replies = replies("variant 2 2\nbest\nvariant 3 3\nbest\nvariant 2 2\nbest\n");
+        assert_eq!(replies[0], "ok variant 2x2x2 connect 2");
+        assert_eq!(replies[1], "bestmove 1 value win:3");
+        assert_eq!(replies[3], "bestmove 5 value win:9");
+        assert_eq!(replies[5], replies[1]);
+    }
+}
@@ -0,0 +1,120 @@
+use crate::play::{self, Engine};
+use crate::render::{self, Highlights, Layout, Style};
+use crate::variant::{Position, Variant};
+use crate::{
+    game_to_number, is_full, is_over, notation, place_new_piece, stored_move_to_human_move,
+    switch_player, Game,
+};
+
+/// The database's stored move, or the search's choice, which prefers the fastest
+/// win and the slowest loss.
+pub fn next_move(engine: &mut Engine, variant: &Variant, g: &Game) -> Result<usize, String> {
+    match engine {
+        Engine::Database(database) => {
+            let state_num = game_to_number(g.clone());
+            match database.lookup(state_num) {
+                Ok(Some(record)) if record.stored_move >= 0 => {
+                    Ok(stored_move_to_human_move(record.stored_move) as usize)
+                }
+                Ok(Some(_)) => Err(format!("State {} has no stored move", state_num)),
+                Ok(None) => Err(format!("State {} is not in the database", state_num)),
+                Err(error) => Err(format!("Could not read the database: {}", error)),
+            }
+        }
+        Engine::Search(_) => engine.choose(variant, g),
+    }
+}
+
+/// Whether `peg` is the only move that keeps the mover's best result.
+fn is_forced(engine: &mut Engine, g: &Game, peg: usize) -> Result<bool, String> {
+    let results = engine.peg_results(g)?;
+    let rank = |winner: i8| match winner {
+        0 => 1,
+        w if w == g.player => 2,
+        _ => 0,
+    };
+    let best = results.iter().map(|&(_, w)| rank(w)).max().unwrap_or(0);
+    let keeping: Vec<usize> = results
+        .iter()
+        .filter(|&&(_, w)| rank(w) == best)
+        .map(|&(p, _)| p)
+        .collect();
+    Ok(keeping == [peg])
+}
+
+fn print_summary(engine: &mut Engine, g: &Game) -> Result<(), String> {
+    let winner = engine.evaluate(g)?;
+    let result = if winner == 0 {
+        "Draw with best play".to_string()
+    } else {
+        format!("Player {} wins with best play", winner)
+    };
+    match engine {
+        Engine::Search(searcher) => {
+            let position = Position::from_game(g);
+            let moves = searcher.solve(&position, None).moves_to_end(&position);
+            match moves {
+                Some(moves) => println!("{}, {} moves from here", result, moves),
+                None => println!("{}", result),
+            }
+        }
+        Engine::Database(_) => println!("{}", result),
+    }
+    Ok(())
+}
+
+fn follow(args: &[String]) -> Result<(), String> {
+    let variant = Variant::new(3, 3).unwrap();
+    let style = Style::from_args(args, Layout::Layers)?;
+    let mut engine = play::open_engine(args, &variant)?;
+    let mut g = match crate::positional(args, 0) {
+        Some(text) => notation::parse_game(text)?,
+        None => notation::parse_game("0")?,
+    };
+    println!("State: {}", game_to_number(g.clone()));
+    render::print(&g, &style, &Highlights::default());
+    print_summary(&mut engine, &g)?;
+
+    let mut ply = 1;
+    let mut moves = Vec::new();
+    while !is_over(&g) && !is_full(&g) {
+        let peg = next_move(&mut engine, &variant, &g)?;
+        let forced = is_forced(&mut engine, &g, peg)?;
+        let player = g.player;
+        if place_new_piece(&mut g.board, peg / 3, peg % 3, player).is_none() {
+            return Err(format!(
+                "Move {} plays on full peg {}",
+                ply,
+                notation::format_peg(peg)
+            ));
+        }
+        g.player = switch_player(player);
+        moves.push(peg);
+        println!(
+            "{}. Player {} plays {}{}, State: {}",
+            ply,
+            player,
+            notation::describe_peg(peg, 3),
+            if forced { ", forced" } else { "" },
+            game_to_number(g.clone())
+        );
+        let highlights = Highlights::for_game(&g, Some(peg));
+        render::print(&g, &style, &highlights);
+        ply += 1;
+    }
+    if is_over(&g) {
+        println!("Player {} wins", switch_player(g.player));
+    } else {
+        println!("Draw, the board is full");
+    }
+    println!("Line: {}", notation::format_moves(&moves));
+    Ok(())
+}
+
+/// `pv [POSITION] [--engine database|search] [--database PATH] [--board LAYOUT]`
+/// `[--colour] [--unicode]`
+pub fn pv(args: &[String]) {
+    if let Err(error) = follow(args) {
+        println!("{}", error);
+    }
+}
@@ -0,0 +1,375 @@
+//! The `connect3` Python module, built with `--features python`. Pegs are 1 to 9
+//! and cells are indexed `[x][y][z]` as in `Board`, with x going Down, y going
+//! Right and z up the peg.
+
+// The #[pymethods] expansion converts PyErr into itself
+#![allow(clippy::useless_conversion)]
+
+use pyo3::exceptions::{PyIOError, PyValueError};
+use pyo3::prelude::*;
+
+use crate::database::{self, Database, Record, Records};
+use crate::stats::{self, DepthStats, Endings, Stats};
+use crate::{
+    board_to_number, game_to_number, game_to_str, get_all_next_numbers, get_move_between_board,
+    is_full, is_over, make_new_board, number_to_board, place_new_piece, stored_move_to_human_move,
+    switch_player, Board, Game, STATES,
+};
+
+fn check_state(state: u64) -> PyResult<()> {
+    if state < STATES {
+        Ok(())
+    } else {
+        Err(PyValueError::new_err(format!(
+            "{} is not a state number",
+            state
+        )))
+    }
+}
+
+/// The 1 based peg a stored move drops into, None for finished games.
+fn stored_peg(stored_move: i8) -> Option<usize> {
+    (stored_move >= 0).then(|| stored_move_to_human_move(stored_move) as usize + 1)
+}
+
+#[pyclass(name = "Board", module = "connect3", eq, hash, frozen)]
+#[derive(Clone, PartialEq, Hash)]
+struct PyBoard {
+    board: Board,
+}
+
+#[pymethods]
+impl PyBoard {
+    /// An empty board, or one from nested `[x][y][z]` lists of 0, 1 and 2.
+    #[new]
+    #[pyo3(signature = (cells=None))]
+    fn new(cells: Option<Vec<Vec<Vec<i8>>>>) -> PyResult<PyBoard> {
+        let mut board = make_new_board();
+        if let Some(cells) = cells {
+            let shape_ok = cells.len() == 3
+                && cells
+                    .iter()
+                    .all(|row| row.len() == 3 && row.iter().all(|peg| peg.len() == 3));
+            if !shape_ok {
+                return Err(PyValueError::new_err("cells must be 3 x 3 x 3"));
+            }
+            for (x, row) in cells.iter().enumerate() {
+                for (y, peg) in row.iter().enumerate() {
+                    for (z, &cell) in peg.iter().enumerate() {
+                        if !(0..=2).contains(&cell) {
+                            return Err(PyValueError::new_err(format!("bad cell {}", cell)));
+                        }
+                        board.data[x][y][z] = cell;
+                    }
+                }
+            }
+        }
+        Ok(PyBoard { board })
+    }
+
+    #[staticmethod]
+    fn from_number(state: u64) -> PyResult<PyBoard> {
+        check_state(state)?;
+        Ok(PyBoard {
+            board: number_to_board(state).board,
+        })
+    }
+
+    fn number(&self) -> u64 {
+        board_to_number(&self.board)
+    }
+
+    #[getter]
+    fn cells(&self) -> Vec<Vec<Vec<i8>>> {
+        self.board
+            .data
+            .iter()
+            .map(|row| row.iter().map(|peg| peg.to_vec()).collect())
+            .collect()
+    }
+
+    fn __getitem__(&self, index: (usize, usize, usize)) -> PyResult<i8> {
+        let (x, y, z) = index;
+        if x < 3 && y < 3 && z < 3 {
+            Ok(self.board.data[x][y][z])
+        } else {
+            Err(PyValueError::new_err(format!("no cell {:?}", index)))
+        }
+    }
+
+    fn __str__(&self) -> String {
+        game_to_str(Game {
+            board: self.board.clone(),
+            player: 1,
+        })
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Board.from_number({})", self.number())
+    }
+}
+
+#[pyclass(name = "Game", module = "connect3", eq, hash, frozen)]
+#[derive(Clone, PartialEq, Hash)]
+struct PyGame {
+    game: Game,
+}
+
+#[pymethods]
+impl PyGame {
+    /// The empty board, or `board` with the side to move taken from the piece
+    /// count unless `player` is given.
+    #[new]
+    #[pyo3(signature = (board=None, player=None))]
+    fn new(board: Option<PyBoard>, player: Option<i8>) -> PyResult<PyGame> {
+        let board = board.map_or_else(make_new_board, |board| board.board);
+        let mut game = number_to_board(board_to_number(&board));
+        if let Some(player) = player {
+            if player != 1 && player != 2 {
+                return Err(PyValueError::new_err("player must be 1 or 2"));
+            }
+            game.player = player;
+        }
+        Ok(PyGame { game })
+    }
+
+    #[staticmethod]
+    fn from_state(state: u64) -> PyResult<PyGame> {
+        check_state(state)?;
+        Ok(PyGame {
+            game: number_to_board(state),
+        })
+    }
+
+    /// Plays pegs 1 to 9 in turn from the empty board.
+    #[staticmethod]
+    fn from_moves(moves: Vec<usize>) -> PyResult<PyGame> {
+        let mut game = PyGame {
+            game: number_to_board(0),
+        };
+        for peg in moves {
+            game = game.play(peg)?;
+        }
+        Ok(game)
+    }
+
+    #[getter]
+    fn state(&self) -> u64 {
+        game_to_number(self.game.clone())
+    }
+
+    #[getter]
+    fn board(&self) -> PyBoard {
+        PyBoard {
+            board: self.game.board.clone(),
+        }
+    }
+
+    #[getter]
+    fn player(&self) -> i8 {
+        self.game.player
+    }
+
+    fn is_over(&self) -> bool {
+        is_over(&self.game)
+    }
+
+    fn is_full(&self) -> bool {
+        is_full(&self.game)
+    }
+
+    /// The player who completed a line, 0 for a full board, None while the game
+    /// goes on.
+    fn winner(&self) -> Option<i8> {
+        if is_over(&self.game) {
+            Some(switch_player(self.game.player))
+        } else if is_full(&self.game) {
+            Some(0)
+        } else {
+            None
+        }
+    }
+
+    fn legal_moves(&self) -> Vec<usize> {
+        if is_over(&self.game) {
+            return Vec::new();
+        }
+        (0..9)
+            .filter(|&peg| self.game.board.data[peg / 3][peg % 3][2] == 0)
+            .map(|peg| peg + 1)
+            .collect()
+    }
+
+    /// The game after the side to move plays `peg`.
+    fn play(&self, peg: usize) -> PyResult<PyGame> {
+        if !(1..=9).contains(&peg) {
+            return Err(PyValueError::new_err(format!("no peg {}", peg)));
+        }
+        if is_over(&self.game) {
+            return Err(PyValueError::new_err("the game is over"));
+        }
+        let mut game = self.game.clone();
+        if place_new_piece(&mut game.board, (peg - 1) / 3, (peg - 1) % 3, game.player).is_none() {
+            return Err(PyValueError::new_err(format!("peg {} is full", peg)));
+        }
+        game.player = switch_player(game.player);
+        Ok(PyGame { game })
+    }
+
+    /// The state numbers one move away, as `get_all_next_numbers` gives them.
+    fn next_states(&self) -> Vec<u64> {
+        if is_over(&self.game) {
+            return Vec::new();
+        }
+        get_all_next_numbers(self.game.clone())
+    }
+
+    fn __str__(&self) -> String {
+        game_to_str(self.game.clone())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("Game.from_state({})", self.state())
+    }
+}
+
+#[pymethods]
+impl Record {
+    /// The peg of the stored move, None for finished games.
+    #[getter]
+    fn peg(&self) -> Option<usize> {
+        stored_peg(self.stored_move)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Record(state={}, stored_move={}, winner={})",
+            self.state, self.stored_move, self.winner
+        )
+    }
+}
+
+#[pyclass(name = "Database", module = "connect3", frozen)]
+struct PyDatabase {
+    database: Database,
+}
+
+#[pymethods]
+impl PyDatabase {
+    #[new]
+    #[pyo3(signature = (path=database::DEFAULT_PATH.to_string()))]
+    fn new(path: String) -> PyResult<PyDatabase> {
+        Database::open(&path)
+            .map(|database| PyDatabase { database })
+            .map_err(|error| PyIOError::new_err(format!("could not open {}: {}", path, error)))
+    }
+
+    fn __len__(&self) -> usize {
+        self.database.len() as usize
+    }
+
+    fn lookup(&self, py: Python, state: u64) -> PyResult<Option<Record>> {
+        py.allow_threads(|| self.database.lookup(state))
+            .map_err(|error| PyIOError::new_err(error.to_string()))
+    }
+
+    /// `(peg, winner)` for a state in the database, with peg None for finished
+    /// games and winner 0 for a draw.
+    fn best_move(&self, py: Python, state: u64) -> PyResult<Option<(Option<usize>, i8)>> {
+        Ok(self
+            .lookup(py, state)?
+            .map(|record| (stored_peg(record.stored_move), record.winner)))
+    }
+
+    /// Every record in file order.
+    fn records(&self) -> PyResult<RecordIter> {
+        self.database
+            .records()
+            .map(|records| RecordIter { records })
+            .map_err(|error| PyIOError::new_err(error.to_string()))
+    }
+
+    /// The `stats` command's tallies. Reads the whole file.
+    fn stats(&self, py: Python) -> PyResult<Stats> {
+        py.allow_threads(|| stats::collect(&self.database, |_, _| {}))
+            .map_err(|error| PyIOError::new_err(error.to_string()))
+    }
+}
+
+#[pyclass(module = "connect3")]
+struct RecordIter {
+    records: Records,
+}
+
+#[pymethods]
+impl RecordIter {
+    fn __iter__(slf: PyRef<Self>) -> PyRef<Self> {
+        slf
+    }
+
+    fn __next__(&mut self) -> PyResult<Option<Record>> {
+        self.records
+            .next()
+            .transpose()
+            .map_err(|error| PyIOError::new_err(error.to_string()))
+    }
+}
+
+#[pymethods]
+impl Stats {
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(self).map_err(|error| PyValueError::new_err(error.to_string()))
+    }
+}
+
+#[pyfunction(name = "number_to_board")]
+fn py_number_to_board(state: u64) -> PyResult<PyGame> {
+    PyGame::from_state(state)
+}
+
+#[pyfunction(name = "board_to_number")]
+fn py_board_to_number(board: PyBoard) -> u64 {
+    board.number()
+}
+
+#[pyfunction(name = "get_all_next_numbers")]
+fn py_get_all_next_numbers(state: u64) -> PyResult<Vec<u64>> {
+    Ok(PyGame::from_state(state)?.next_states())
+}
+
+#[pyfunction(name = "is_over")]
+fn py_is_over(state: u64) -> PyResult<bool> {
+    Ok(PyGame::from_state(state)?.is_over())
+}
+
+#[pyfunction(name = "get_move_between_board")]
+fn py_get_move_between_board(before: u64, after: u64) -> PyResult<i8> {
+    if after <= before || after >= STATES {
+        return Err(PyValueError::new_err("after must be a later state"));
+    }
+    Ok(get_move_between_board(before, after))
+}
+
+#[pyfunction]
+fn stored_move_to_peg(stored_move: i8) -> Option<usize> {
+    stored_peg(stored_move)
+}
+
+#[pymodule]
+fn connect3(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_class::<PyBoard>()?;
+    module.add_class::<PyGame>()?;
+    module.add_class::<PyDatabase>()?;
+    module.add_class::<Record>()?;
+    module.add_class::<RecordIter>()?;
+    module.add_class::<Stats>()?;
+    module.add_class::<DepthStats>()?;
+    module.add_class::<Endings>()?;
+    module.add_function(wrap_pyfunction!(py_number_to_board, module)?)?;
+    module.add_function(wrap_pyfunction!(py_board_to_number, module)?)?;
+    module.add_function(wrap_pyfunction!(py_get_all_next_numbers, module)?)?;
+    module.add_function(wrap_pyfunction!(py_is_over, module)?)?;
+    module.add_function(wrap_pyfunction!(py_get_move_between_board, module)?)?;
+    module.add_function(wrap_pyfunction!(stored_move_to_peg, module)?)?;
+    Ok(())
+}
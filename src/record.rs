@@ -0,0 +1,433 @@
+use std::fmt;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::play::{self, prompt};
+use crate::render::{self, Highlights, Layout, Style};
+use crate::variant::{Position, Variant};
+use crate::{notation, number_to_board};
+
+/// Headers every written record starts with, in this order.
+const STANDARD_HEADERS: [&str; 5] = ["Player1", "Player2", "Date", "Variant", "Result"];
+
+pub struct RecordMove {
+    pub peg: usize,
+    /// Suffix like ! or ?? written straight after the move
+    pub mark: String,
+    pub comment: Option<String>,
+}
+
+/// A game in a PGN-like text format: `[Name "value"]` header lines, then the
+/// moves with optional move numbers, marks and `{comments}`, then the result.
+///
+/// ```text
+/// [Player1 "Alice"]
+/// [Player2 "Bob"]
+/// [Date "2024.05.01"]
+/// [Variant "3x3x3 connect 3"]
+/// [Result "1-0"]
+///
+/// 1. 5 1 2. 9 5 3. 2 8 4. 3 1-0
+/// ```
+///
+/// Results are 1-0, 0-1, 1/2-1/2 for a full board, or * for an unfinished game.
+/// A plain move list like "5 1 9 5" is also a record.
+pub struct GameRecord {
+    pub headers: Vec<(String, String)>,
+    pub moves: Vec<RecordMove>,
+}
+
+/// Today as YYYY.MM.DD, from days since 1970 with the civil calendar algorithm.
+fn today() -> String {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() / 86400) as i64;
+    let shifted = days + 719468;
+    let era = shifted / 146097;
+    let day_of_era = shifted - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!("{:04}.{:02}.{:02}", year, month, day)
+}
+
+/// The result token for the position the moves reach.
+pub fn result_of(variant: &Variant, position: &Position) -> &'static str {
+    match variant.winner(position) {
+        Some(1) => "1-0",
+        Some(_) => "0-1",
+        None if variant.is_full(position) => "1/2-1/2",
+        None => "*",
+    }
+}
+
+fn is_result(token: &str) -> bool {
+    matches!(token, "1-0" | "0-1" | "1/2-1/2" | "*")
+}
+
+impl GameRecord {
+    /// A record of the moves with the standard headers filled in.
+    pub fn new(pegs: &[usize]) -> GameRecord {
+        let variant = Variant::new(3, 3).unwrap();
+        let position = notation::play_moves(&variant, pegs).unwrap();
+        let mut record = GameRecord {
+            headers: Vec::new(),
+            moves: pegs
+                .iter()
+                .map(|&peg| RecordMove {
+                    peg,
+                    mark: String::new(),
+                    comment: None,
+                })
+                .collect(),
+        };
+        record.set_header("Player1", "?");
+        record.set_header("Player2", "?");
+        record.set_header("Date", &today());
+        record.set_header("Variant", &variant.to_string());
+        record.set_header("Result", result_of(&variant, &position));
+        record
+    }
+
+    pub fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn set_header(&mut self, name: &str, value: &str) {
+        match self.headers.iter_mut().find(|(key, _)| key == name) {
+            Some(header) => header.1 = value.to_string(),
+            None => self.headers.push((name.to_string(), value.to_string())),
+        }
+    }
+
+    pub fn pegs(&self) -> Vec<usize> {
+        self.moves
+            .iter()
+            .map(|record_move| record_move.peg)
+            .collect()
+    }
+
+    pub fn variant(&self) -> Result<Variant, String> {
+        match self.header("Variant") {
+            Some(variant) => variant.parse(),
+            None => Ok(Variant::new(3, 3).unwrap()),
+        }
+    }
+
+    /// Reads a record and replays its moves, refusing illegal moves and a result
+    /// that disagrees with a finished game.
+    pub fn parse(text: &str) -> Result<GameRecord, String> {
+        let mut record = GameRecord {
+            headers: Vec::new(),
+            moves: Vec::new(),
+        };
+        let mut body = String::new();
+        for (index, line) in text.lines().enumerate() {
+            let trimmed = line.trim();
+            // Headers only come before the moves
+            if !body.trim().is_empty() || !trimmed.starts_with('[') {
+                body.push_str(line);
+                body.push('\n');
+                continue;
+            }
+            let header = trimmed
+                .strip_prefix('[')
+                .and_then(|header| header.strip_suffix(']'))
+                .and_then(|header| header.split_once(' '))
+                .and_then(|(name, value)| {
+                    let value = value.trim().strip_prefix('"')?.strip_suffix('"')?;
+                    Some((name, value.replace("\\\"", "\"").replace("\\\\", "\\")))
+                });
+            match header {
+                Some((name, value)) => record.set_header(name, &value),
+                None => return Err(format!("Line {}: bad header {}", index + 1, trimmed)),
+            }
+        }
+
+        let variant = record.variant()?;
+        let mut result = None;
+        let mut rest = body.as_str();
+        while let Some(start) = rest.find(|c: char| !c.is_whitespace()) {
+            rest = &rest[start..];
+            if let Some(comment) = rest.strip_prefix('{') {
+                let end = comment.find('}').ok_or("Unclosed { comment")?;
+                let text = comment[..end].trim().to_string();
+                if let Some(last) = record.moves.last_mut() {
+                    last.comment = Some(text);
+                }
+                rest = &comment[end + 1..];
+                continue;
+            }
+            let end = rest
+                .find(|c: char| c.is_whitespace() || c == '{')
+                .unwrap_or(rest.len());
+            let token = &rest[..end];
+            rest = &rest[end..];
+            if is_result(token) {
+                result = Some(token.to_string());
+            } else if token.ends_with('.')
+                && token
+                    .trim_end_matches('.')
+                    .chars()
+                    .all(|c| c.is_ascii_digit())
+            {
+                // Move numbers are only for people reading the record
+            } else {
+                let peg_text = token.trim_end_matches(['!', '?']);
+                record.moves.push(RecordMove {
+                    peg: notation::parse_peg(peg_text, variant.size)?,
+                    mark: token[peg_text.len()..].to_string(),
+                    comment: None,
+                });
+            }
+        }
+
+        let position = notation::play_moves(&variant, &record.pegs())?;
+        let played = result_of(&variant, &position);
+        let header = record.header("Result").map(str::to_string);
+        for claimed in header.iter().chain(result.iter()) {
+            if played != "*" && claimed != played {
+                return Err(format!(
+                    "The result says {} but the moves end in {}",
+                    claimed, played
+                ));
+            }
+        }
+        if let (Some(header), Some(result)) = (&header, &result) {
+            if header != result {
+                return Err(format!(
+                    "The Result header says {} but the move list ends with {}",
+                    header, result
+                ));
+            }
+        }
+        if header.is_none() {
+            record.set_header("Result", result.as_deref().unwrap_or(played));
+        }
+        Ok(record)
+    }
+
+    pub fn read(path: &str) -> Result<GameRecord, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|error| format!("Could not open {}: {}", path, error))?;
+        GameRecord::parse(&text)
+    }
+
+    pub fn write(&self, path: &str) -> std::io::Result<()> {
+        std::fs::write(path, self.to_string())
+    }
+}
+
+impl fmt::Display for GameRecord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut headers: Vec<&(String, String)> = Vec::new();
+        for name in STANDARD_HEADERS {
+            headers.extend(self.headers.iter().filter(|(key, _)| key == name));
+        }
+        headers.extend(
+            self.headers
+                .iter()
+                .filter(|(key, _)| !STANDARD_HEADERS.contains(&key.as_str())),
+        );
+        for (name, value) in headers {
+            let value = value.replace('\\', "\\\\").replace('"', "\\\"");
+            writeln!(f, "[{} \"{}\"]", name, value)?;
+        }
+        writeln!(f)?;
+
+        let mut tokens = Vec::new();
+        for (index, record_move) in self.moves.iter().enumerate() {
+            if index % 2 == 0 {
+                tokens.push(format!("{}.", index / 2 + 1));
+            }
+            tokens.push(format!(
+                "{}{}",
+                notation::format_peg(record_move.peg),
+                record_move.mark
+            ));
+            if let Some(comment) = &record_move.comment {
+                tokens.push(format!("{{{}}}", comment));
+            }
+        }
+        tokens.push(self.header("Result").unwrap_or("*").to_string());
+        let mut line = String::new();
+        for token in tokens {
+            if !line.is_empty() && line.len() + token.len() >= 80 {
+                writeln!(f, "{}", line)?;
+                line.clear();
+            }
+            if !line.is_empty() {
+                line.push(' ');
+            }
+            line.push_str(&token);
+        }
+        writeln!(f, "{}", line)
+    }
+}
+
+fn show_ply(
+    record: &GameRecord,
+    ply: usize,
+    engine: &mut Option<play::Engine>,
+    style: &Style,
+) -> Result<(), String> {
+    let variant = Variant::new(3, 3).unwrap();
+    let pegs = record.pegs();
+    let position = notation::play_moves(&variant, &pegs[..ply])?;
+    let g = number_to_board(position.state_number());
+    let last_move = ply.checked_sub(1).map(|index| pegs[index]);
+    match last_move {
+        Some(peg) => println!(
+            "Ply {} of {}: player {} played {}{}, State: {}",
+            ply,
+            pegs.len(),
+            2 - ply % 2,
+            notation::describe_peg(peg, 3),
+            record.moves[ply - 1].mark,
+            position.state_number()
+        ),
+        None => println!("Start of the game, {} moves", pegs.len()),
+    }
+    if let Some(comment) = ply
+        .checked_sub(1)
+        .and_then(|index| record.moves[index].comment.as_ref())
+    {
+        println!("{{{}}}", comment);
+    }
+    render::print(&g, style, &Highlights::for_game(&g, last_move));
+    if let Some(engine) = engine.as_mut() {
+        match engine.evaluate(&g) {
+            Ok(0) => println!("Evaluation: draw with best play"),
+            Ok(winner) => println!("Evaluation: player {} wins with best play", winner),
+            Err(error) => println!("Evaluation unavailable: {}", error),
+        }
+    }
+    Ok(())
+}
+
+/// `replay FILE [--print] [--engine database|search] [--database PATH] [--no-eval]`
+/// `[--board LAYOUT] [--colour] [--unicode]`
+pub fn replay(args: &[String]) {
+    let variant = Variant::new(3, 3).unwrap();
+    let record = match crate::positional(args, 0).ok_or("Give a record file".to_string()) {
+        Ok(path) => GameRecord::read(path),
+        Err(error) => Err(error),
+    };
+    let setup = record.and_then(|record| {
+        if !record.variant()?.is_standard() {
+            return Err("Only 3x3x3 connect 3 games can be replayed".to_string());
+        }
+        let style = Style::from_args(args, Layout::Layers)?;
+        let engine = if crate::has_switch(args, "--no-eval") {
+            None
+        } else {
+            Some(play::open_engine(args, &variant)?)
+        };
+        Ok((record, style, engine))
+    });
+    let (record, style, mut engine) = match setup {
+        Ok(setup) => setup,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    for (name, value) in &record.headers {
+        println!("{}: {}", name, value);
+    }
+
+    let last = record.moves.len();
+    if crate::has_switch(args, "--print") {
+        for ply in 0..=last {
+            if let Err(error) = show_ply(&record, ply, &mut engine, &style) {
+                println!("{}", error);
+                return;
+            }
+        }
+        return;
+    }
+    let mut ply = 0;
+    loop {
+        if let Err(error) = show_ply(&record, ply, &mut engine, &style) {
+            println!("{}", error);
+            return;
+        }
+        let input = match prompt("n (next), p (previous), first, last, a ply number, q> ") {
+            Some(input) => input,
+            None => return,
+        };
+        match input.as_str() {
+            "" | "n" | "next" => ply = (ply + 1).min(last),
+            "p" | "prev" | "previous" => ply = ply.saturating_sub(1),
+            "first" => ply = 0,
+            "last" => ply = last,
+            "q" | "quit" => return,
+            number => match number.parse::<usize>() {
+                Ok(number) if number <= last => ply = number,
+                _ => println!("Plies go from 0 to {}", last),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECORD: &str = r#"[Player1 "Ann \"the wall\" Lee"]
+[Player2 "C:\\bots\\greedy"]
+[Date "2024.05.01"]
+[Variant "3x3x3 connect 3"]
+[Result "1-0"]
+[Event "Club night"]
+
+1. 5 1?! {too slow} 2. 9?? {win to loss} 5 3. 2 8 4. 3 1-0
+"#;
+
+    #[test]
+    fn records_read_back_as_written() {
+        let record = GameRecord::parse(RECORD).unwrap();
+        assert_eq!(record.header("Player1"), Some("Ann \"the wall\" Lee"));
+        assert_eq!(record.header("Player2"), Some("C:\\bots\\greedy"));
+        assert_eq!(record.to_string(), RECORD);
+        let again = GameRecord::parse(&record.to_string()).unwrap();
+        assert_eq!(again.headers, record.headers);
+        assert_eq!(again.pegs(), record.pegs());
+        for (read, written) in again.moves.iter().zip(&record.moves) {
+            assert_eq!(read.mark, written.mark);
+            assert_eq!(read.comment, written.comment);
+        }
+    }
+
+    #[test]
+    fn illegal_moves_are_refused() {
+        assert!(GameRecord::parse("5 5 5 5").is_err());
+        assert!(GameRecord::parse("5 10").is_err());
+        // Play after player 1 completes the row 1 2 3
+        assert!(GameRecord::parse("1 5 2 9 3 4").is_err());
+    }
+
+    #[test]
+    fn results_must_match_the_game() {
+        assert!(GameRecord::parse("1 5 2 9 3 0-1").is_err());
+        assert!(GameRecord::parse("[Result \"1/2-1/2\"]\n1 5 2 9 3").is_err());
+        assert!(GameRecord::parse("[Result \"0-1\"]\n5 1 1-0").is_err());
+        assert!(GameRecord::parse("1 5 2 9 3 1-0").is_ok());
+    }
+
+    #[test]
+    fn headers_end_where_the_moves_start() {
+        let record = GameRecord::parse("[Event \"A\"]\n5 1\n[Event \"B\"]\n");
+        assert!(record.is_err());
+    }
+}
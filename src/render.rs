@@ -0,0 +1,269 @@
+use crate::notation;
+use crate::variant::{Position, Variant};
+use crate::{game_to_str, Game};
+
+const RESET: &str = "\x1b[0m";
+const PLAYER_COLOURS: [&str; 2] = ["\x1b[31m", "\x1b[34m"];
+const LINE_COLOUR: &str = "\x1b[1;32m";
+const LAST_COLOUR: &str = "\x1b[1m";
+const BEST_COLOUR: &str = "\x1b[1;33m";
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Layout {
+    /// The plain `game_to_str` grid, which `convert` and `solve` read back
+    Digits,
+    /// The three layers next to each other, bottom first
+    Layers,
+    /// Each peg's pieces from the bottom up, laid out like the board
+    Pegs,
+}
+
+#[derive(Clone, Copy)]
+pub struct Style {
+    pub layout: Layout,
+    pub colour: bool,
+    pub unicode: bool,
+}
+
+impl Style {
+    /// `--board digits|layers|pegs`, `--colour` and `--unicode`.
+    pub fn from_args(args: &[String], layout: Layout) -> Result<Style, String> {
+        let layout = match crate::flag_value(args, "--board") {
+            None => layout,
+            Some("digits") => Layout::Digits,
+            Some("layers") => Layout::Layers,
+            Some("pegs") => Layout::Pegs,
+            Some(other) => {
+                return Err(format!(
+                    "Unknown board layout {}, use digits, layers or pegs",
+                    other
+                ))
+            }
+        };
+        let style = Style {
+            layout,
+            colour: crate::has_switch(args, "--colour"),
+            unicode: crate::has_switch(args, "--unicode"),
+        };
+        if layout == Layout::Digits && (style.colour || style.unicode) {
+            return Err(
+                "The digits layout stays plain, use --board layers or pegs for --colour and --unicode"
+                    .to_string(),
+            );
+        }
+        Ok(style)
+    }
+}
+
+/// Pegs are counted from 0 and cells are `(peg, height)`.
+#[derive(Default)]
+pub struct Highlights {
+    pub last_move: Option<usize>,
+    pub best_move: Option<usize>,
+    pub line: Vec<(usize, usize)>,
+}
+
+impl Highlights {
+    /// The last move, plus the winning line if the game is over.
+    pub fn for_game(g: &Game, last_move: Option<usize>) -> Highlights {
+        Highlights {
+            last_move,
+            best_move: None,
+            line: winning_cells(g),
+        }
+    }
+}
+
+/// The cells of a line that ended the game, empty if nobody has won.
+pub fn winning_cells(g: &Game) -> Vec<(usize, usize)> {
+    let variant = Variant::new(3, 3).unwrap();
+    let position = Position::from_game(g);
+    let line = variant
+        .winner(&position)
+        .and_then(|winner| {
+            variant
+                .completed_lines(position.pieces[(winner - 1) as usize])
+                .next()
+        })
+        .map_or(0, |(line, _)| line);
+    let mut cells = Vec::new();
+    for peg in 0..9 {
+        for z in 0..3 {
+            if line & (1 << variant.cell(peg, z)) != 0 {
+                cells.push((peg, z));
+            }
+        }
+    }
+    cells
+}
+
+fn height(g: &Game, peg: usize) -> usize {
+    g.board.data[peg / 3][peg % 3]
+        .iter()
+        .take_while(|&&v| v != 0)
+        .count()
+}
+
+/// One cell as three columns: [X] on the winning line, (X) for the last move and
+/// + where the best move would land.
+fn cell(g: &Game, style: &Style, highlights: &Highlights, peg: usize, z: usize) -> String {
+    let value = g.board.data[peg / 3][peg % 3][z];
+    let symbol = match (value, style.unicode) {
+        (1, false) => "X",
+        (2, false) => "O",
+        (_, false) => ".",
+        (1, true) => "●",
+        (2, true) => "○",
+        (_, true) => "·",
+    };
+    let piece = if value != 0 && style.colour {
+        format!(
+            "{}{}{}",
+            PLAYER_COLOURS[(value - 1) as usize],
+            symbol,
+            RESET
+        )
+    } else {
+        symbol.to_string()
+    };
+    let top = height(g, peg);
+    let (open, close, colour) = if highlights.line.contains(&(peg, z)) {
+        ("[", "]", LINE_COLOUR)
+    } else if highlights.last_move == Some(peg) && z + 1 == top {
+        ("(", ")", LAST_COLOUR)
+    } else if highlights.best_move == Some(peg) && z == top {
+        return if style.colour {
+            format!(" {}+{} ", BEST_COLOUR, RESET)
+        } else {
+            " + ".to_string()
+        };
+    } else {
+        return format!(" {} ", piece);
+    };
+    if style.colour {
+        format!(
+            "{}{}{}{}{}{}{}",
+            colour, open, RESET, piece, colour, close, RESET
+        )
+    } else {
+        format!("{}{}{}", open, piece, close)
+    }
+}
+
+fn layers(g: &Game, style: &Style, highlights: &Highlights) -> String {
+    let mut output = format!("{:<14}{:<14}{}\n", "Bottom", "Middle", "Top");
+    output.push_str(["   a  b  c "; 3].join("   ").trim_end());
+    output.push('\n');
+    for x in 0..3 {
+        let rows: Vec<String> = (0..3)
+            .map(|z| {
+                let cells: String = (0..3)
+                    .map(|y| cell(g, style, highlights, x * 3 + y, z))
+                    .collect();
+                format!("{} {}", x + 1, cells)
+            })
+            .collect();
+        output.push_str(rows.join("   ").trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+fn pegs(g: &Game, style: &Style, highlights: &Highlights) -> String {
+    let mut output = "Pegs from the bottom up\n".to_string();
+    output.push_str(&format!("   {:<10}{:<10}{}\n", "a", "b", "c"));
+    for x in 0..3 {
+        let stacks: Vec<String> = (0..3)
+            .map(|y| {
+                (0..3)
+                    .map(|z| cell(g, style, highlights, x * 3 + y, z))
+                    .collect()
+            })
+            .collect();
+        output.push_str(format!("{} {}", x + 1, stacks.join(" ")).trim_end());
+        output.push('\n');
+    }
+    output
+}
+
+pub fn render(g: &Game, style: &Style, highlights: &Highlights) -> String {
+    match style.layout {
+        Layout::Digits => game_to_str(g.clone()),
+        Layout::Layers => layers(g, style, highlights),
+        Layout::Pegs => pegs(g, style, highlights),
+    }
+}
+
+/// The highlights as text for the digits layout, which has to stay plain for
+/// `convert` and `solve` to read back. The other layouts mark them on the board.
+pub fn describe(style: &Style, highlights: &Highlights) -> Vec<String> {
+    let mut lines = Vec::new();
+    if style.layout != Layout::Digits {
+        return lines;
+    }
+    if let Some(peg) = highlights.last_move {
+        lines.push(format!("Last move: {}", notation::describe_peg(peg, 3)));
+    }
+    if let Some(peg) = highlights.best_move {
+        lines.push(format!("Best move: {}", notation::describe_peg(peg, 3)));
+    }
+    if !highlights.line.is_empty() {
+        let cells: Vec<String> = highlights
+            .line
+            .iter()
+            .map(|&(peg, z)| format!("{} height {}", notation::format_square(peg, 3), z + 1))
+            .collect();
+        lines.push(format!("Winning line: {}", cells.join(", ")));
+    }
+    lines
+}
+
+/// Prints the board followed by any highlights the layout cannot show.
+pub fn print(g: &Game, style: &Style, highlights: &Highlights) {
+    println!("{}", render(g, style, highlights));
+    for line in describe(style, highlights) {
+        println!("{}", line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(str::to_string).collect()
+    }
+
+    #[test]
+    fn digits_refuse_colour_and_unicode() {
+        for flags in ["--colour", "--unicode", "--board digits --colour"] {
+            assert!(
+                Style::from_args(&args(flags), Layout::Digits).is_err(),
+                "{}",
+                flags
+            );
+        }
+        assert!(Style::from_args(&args("--board layers --colour"), Layout::Digits).is_ok());
+        assert!(Style::from_args(&args("--board digits"), Layout::Layers).is_ok());
+    }
+
+    #[test]
+    fn digits_describe_the_highlights_under_the_board() {
+        let highlights = Highlights {
+            last_move: Some(4),
+            best_move: Some(0),
+            line: vec![(0, 0), (4, 0), (8, 0)],
+        };
+        let digits = Style::from_args(&[], Layout::Digits).unwrap();
+        assert_eq!(
+            describe(&digits, &highlights),
+            vec![
+                "Last move: 5 (b2)",
+                "Best move: 1 (a1)",
+                "Winning line: a1 height 1, b2 height 1, c3 height 1",
+            ]
+        );
+        let layers = Style::from_args(&[], Layout::Layers).unwrap();
+        assert!(describe(&layers, &highlights).is_empty());
+    }
+}
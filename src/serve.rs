@@ -0,0 +1,496 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam::channel;
+use serde::Serialize;
+
+use crate::convert;
+use crate::database::{self, Database};
+use crate::play::Engine;
+use crate::pv;
+use crate::render::{self, Highlights, Layout, Style};
+use crate::variant::Variant;
+use crate::{game_to_number, is_full, is_over, notation, place_new_piece, switch_player, Game};
+
+const DEFAULT_PORT: u16 = 3327;
+/// Threads answering requests, and connections left waiting for one before the
+/// server stops accepting more.
+const WORKERS: usize = 8;
+const QUEUE: usize = 64;
+
+struct Request {
+    method: String,
+    path: String,
+    query: Vec<(String, String)>,
+}
+
+impl Request {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.query
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// An HTTP status and the message sent back as `{"error": ...}`.
+type Failure = (u16, String);
+
+fn bad_request(message: String) -> Failure {
+    (400, message)
+}
+
+#[derive(Serialize)]
+struct ErrorBody {
+    error: String,
+}
+
+#[derive(Serialize)]
+struct Best {
+    state: u64,
+    player: i8,
+    /// 1 to 9, null once the game is over
+    best: Option<usize>,
+    /// The winner under perfect play, 0 for a draw
+    winner: i8,
+}
+
+#[derive(Serialize)]
+struct PegValue {
+    peg: usize,
+    square: String,
+    /// win, draw or loss for the side to move, or full
+    result: &'static str,
+}
+
+#[derive(Serialize)]
+struct Values {
+    state: u64,
+    player: i8,
+    values: Vec<PegValue>,
+}
+
+#[derive(Serialize)]
+struct Validation {
+    valid: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    state: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    player: Option<i8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    moves: Option<Vec<usize>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stacks: Option<String>,
+    /// Who has won, 0 for a full board, null while the game goes on
+    #[serde(skip_serializing_if = "Option::is_none")]
+    finished: Option<Option<i8>>,
+}
+
+#[derive(Serialize)]
+struct Rendering {
+    state: u64,
+    board: String,
+    /// Highlights the layout cannot show, as `convert` prints them
+    notes: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct Line {
+    state: u64,
+    winner: i8,
+    moves: Vec<usize>,
+    /// The state after each move
+    states: Vec<u64>,
+}
+
+/// `%XX` escapes and `+` for a space, as browsers send query strings.
+fn decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'+' => decoded.push(b' '),
+            b'%' => {
+                let byte = text
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or(format!("Bad escape in {}", text))?;
+                decoded.push(byte);
+                i += 2;
+            }
+            byte => decoded.push(byte),
+        }
+        i += 1;
+    }
+    String::from_utf8(decoded).map_err(|_| format!("{} is not UTF-8", text))
+}
+
+/// Reads the request line and skips the headers. Bodies are never needed.
+fn read_request(stream: &TcpStream) -> Result<Request, String> {
+    let mut reader = BufReader::new(stream);
+    let mut line = String::new();
+    reader
+        .read_line(&mut line)
+        .map_err(|error| error.to_string())?;
+    let mut words = line.split_whitespace();
+    let (method, target) = match (words.next(), words.next()) {
+        (Some(method), Some(target)) => (method.to_string(), target.to_string()),
+        _ => return Err("Bad request line".to_string()),
+    };
+    loop {
+        let mut header = String::new();
+        match reader.read_line(&mut header) {
+            Ok(0) => break,
+            Ok(_) if header.trim().is_empty() => break,
+            Ok(_) => {}
+            Err(error) => return Err(error.to_string()),
+        }
+    }
+
+    let (path, query) = target.split_once('?').unwrap_or((&target, ""));
+    let mut pairs = Vec::new();
+    for pair in query.split('&').filter(|pair| !pair.is_empty()) {
+        let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+        pairs.push((decode(key)?, decode(value)?));
+    }
+    Ok(Request {
+        method,
+        path: decode(path)?,
+        query: pairs,
+    })
+}
+
+fn respond(mut stream: TcpStream, status: u16, body: &str) {
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    let response = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    );
+    // The client may already have gone; there is nobody left to tell
+    let _ = stream.write_all(response.as_bytes());
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, Failure> {
+    serde_json::to_string(value).map_err(|error| (500, error.to_string()))
+}
+
+/// The `position` parameter in any format `convert` reads, the empty board if
+/// it is missing, with the moves that reach it. Positions no game can reach are
+/// refused.
+fn position(request: &Request) -> Result<(Game, Vec<usize>), Failure> {
+    convert::parse_with_moves(request.param("position").unwrap_or("0")).map_err(bad_request)
+}
+
+/// The winner of a finished game, 0 for a full board.
+fn finished(g: &Game) -> Option<i8> {
+    if is_over(g) {
+        Some(switch_player(g.player))
+    } else if is_full(g) {
+        Some(0)
+    } else {
+        None
+    }
+}
+
+fn best(engine: &mut Engine, variant: &Variant, request: &Request) -> Result<String, Failure> {
+    let (g, _) = position(request)?;
+    let state = game_to_number(g.clone());
+    let (best, winner) = match finished(&g) {
+        Some(winner) => (None, winner),
+        None => {
+            let peg = pv::next_move(engine, variant, &g).map_err(|error| (500, error))?;
+            let winner = engine.evaluate(&g).map_err(|error| (500, error))?;
+            (Some(peg + 1), winner)
+        }
+    };
+    to_json(&Best {
+        state,
+        player: g.player,
+        best,
+        winner,
+    })
+}
+
+fn values(engine: &mut Engine, request: &Request) -> Result<String, Failure> {
+    let (g, _) = position(request)?;
+    if finished(&g).is_some() {
+        return Err(bad_request("The game is over".to_string()));
+    }
+    let mut values: Vec<PegValue> = (0..9)
+        .map(|peg| PegValue {
+            peg: peg + 1,
+            square: notation::format_square(peg, 3),
+            result: "full",
+        })
+        .collect();
+    for (peg, winner) in engine.peg_results(&g).map_err(|error| (500, error))? {
+        values[peg].result = match winner {
+            0 => "draw",
+            w if w == g.player => "win",
+            _ => "loss",
+        };
+    }
+    to_json(&Values {
+        state: game_to_number(g.clone()),
+        player: g.player,
+        values,
+    })
+}
+
+/// Always answers 200, with `valid` saying whether the position is reachable.
+fn validate(request: &Request) -> Result<String, Failure> {
+    let validation = match position(request) {
+        Ok((g, moves)) => Validation {
+            valid: true,
+            error: None,
+            state: Some(game_to_number(g.clone())),
+            player: Some(g.player),
+            moves: Some(moves.iter().map(|peg| peg + 1).collect()),
+            stacks: Some(convert::format_stacks(&g)),
+            finished: Some(finished(&g)),
+        },
+        Err((_, error)) => Validation {
+            valid: false,
+            error: Some(error),
+            state: None,
+            player: None,
+            moves: None,
+            stacks: None,
+            finished: None,
+        },
+    };
+    to_json(&validation)
+}
+
+fn render_board(request: &Request) -> Result<String, Failure> {
+    let (g, moves) = position(request)?;
+    let mut args = Vec::new();
+    if let Some(layout) = request.param("board") {
+        args.push("--board".to_string());
+        args.push(layout.to_string());
+    }
+    if request.param("unicode").is_some() {
+        args.push("--unicode".to_string());
+    }
+    let style = Style::from_args(&args, Layout::Layers).map_err(bad_request)?;
+    let highlights = Highlights::for_game(&g, moves.last().copied());
+    to_json(&Rendering {
+        state: game_to_number(g.clone()),
+        board: render::render(&g, &style, &highlights),
+        notes: render::describe(&style, &highlights),
+    })
+}
+
+fn principal_variation(
+    engine: &mut Engine,
+    variant: &Variant,
+    request: &Request,
+) -> Result<String, Failure> {
+    let (mut g, _) = position(request)?;
+    let state = game_to_number(g.clone());
+    let winner = match finished(&g) {
+        Some(winner) => winner,
+        None => engine.evaluate(&g).map_err(|error| (500, error))?,
+    };
+    let mut line = Line {
+        state,
+        winner,
+        moves: Vec::new(),
+        states: Vec::new(),
+    };
+    while finished(&g).is_none() {
+        let peg = pv::next_move(engine, variant, &g).map_err(|error| (500, error))?;
+        if place_new_piece(&mut g.board, peg / 3, peg % 3, g.player).is_none() {
+            return Err((500, format!("The database plays on full peg {}", peg + 1)));
+        }
+        g.player = switch_player(g.player);
+        line.moves.push(peg + 1);
+        line.states.push(game_to_number(g.clone()));
+    }
+    to_json(&line)
+}
+
+fn route(database: &Arc<Database>, request: &Request) -> Result<String, Failure> {
+    if request.method != "GET" {
+        return Err((405, format!("{} is not supported, use GET", request.method)));
+    }
+    let variant = Variant::new(3, 3).unwrap();
+    let mut engine = Engine::Database(Arc::clone(database));
+    match request.path.as_str() {
+        "/best" => best(&mut engine, &variant, request),
+        "/values" => values(&mut engine, request),
+        "/validate" => validate(request),
+        "/render" => render_board(request),
+        "/pv" => principal_variation(&mut engine, &variant, request),
+        other => Err((404, format!("No endpoint {}", other))),
+    }
+}
+
+fn handle(database: &Arc<Database>, stream: TcpStream) {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(10)));
+    let result = read_request(&stream)
+        .map_err(bad_request)
+        .and_then(|request| route(database, &request));
+    let (status, body) = match result {
+        Ok(body) => (200, body),
+        Err((status, error)) => (status, serde_json::to_string(&ErrorBody { error }).unwrap()),
+    };
+    respond(stream, status, &body);
+}
+
+/// Hands each connection to a fixed set of workers sharing one database handle.
+fn listen(listener: TcpListener, database: Arc<Database>) {
+    let (sender, receiver) = channel::bounded::<TcpStream>(QUEUE);
+    for _ in 0..WORKERS {
+        let receiver = receiver.clone();
+        let database = Arc::clone(&database);
+        thread::spawn(move || {
+            for stream in receiver {
+                handle(&database, stream);
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        match stream {
+            // Blocks while the queue is full, leaving new clients in the backlog
+            Ok(stream) => sender.send(stream).unwrap(),
+            Err(error) => println!("Connection failed: {}", error),
+        }
+    }
+}
+
+/// `serve [--database PATH] [--port N]`
+///
+/// Answers HTTP GET requests on localhost with JSON from a small pool of threads
+/// sharing one database handle. Every endpoint takes `position` as a state
+/// number, move sequence, grid or stacks string.
+pub fn serve(args: &[String]) {
+    let path = crate::flag_value(args, "--database").unwrap_or(database::DEFAULT_PATH);
+    let port = match crate::flag_value(args, "--port").map(str::parse) {
+        None => DEFAULT_PORT,
+        Some(Ok(port)) => port,
+        Some(Err(_)) => {
+            println!("Bad port: {}", crate::flag_value(args, "--port").unwrap());
+            return;
+        }
+    };
+    let database = match Database::open(path) {
+        Ok(database) => Arc::new(database),
+        Err(error) => {
+            println!("Could not open {}: {}", path, error);
+            return;
+        }
+    };
+    let listener = match TcpListener::bind(("127.0.0.1", port)) {
+        Ok(listener) => listener,
+        Err(error) => {
+            println!("Could not listen on port {}: {}", port, error);
+            return;
+        }
+    };
+    println!("Serving {} on http://127.0.0.1:{}", path, port);
+    listen(listener, database);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::net::SocketAddr;
+
+    use crate::negamax::Searcher;
+    use crate::test_support::{temp_path, write_database};
+
+    const ROOT: &str = "5 8 8 3 6 2 3 4 7 7";
+
+    /// A server on a free port answering from a database of the game after `ROOT`.
+    fn start() -> SocketAddr {
+        let variant = Variant::new(3, 3).unwrap();
+        let root = notation::play_moves(&variant, &notation::parse_moves(ROOT, 3).unwrap());
+        let path = temp_path("serve.bin");
+        write_database(&variant, root.unwrap(), &path);
+        let database = Arc::new(Database::open(&path).unwrap());
+        std::fs::remove_file(path).unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || listen(listener, database));
+        address
+    }
+
+    fn get(address: SocketAddr, method: &str, target: &str) -> (u16, serde_json::Value) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: test\r\n\r\n",
+            method, target
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response.split_whitespace().nth(1).unwrap().parse().unwrap();
+        let body = response.split_once("\r\n\r\n").unwrap().1;
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    #[test]
+    fn endpoints_answer_from_the_database() {
+        let address = start();
+
+        let (status, body) = get(address, "GET", "/validate?position=5+1+9");
+        assert_eq!(status, 200);
+        assert_eq!(body["valid"], true);
+        assert_eq!(body["moves"], serde_json::json!([5, 1, 9]));
+        let (status, body) = get(address, "GET", "/validate?position=19683");
+        assert_eq!(status, 200);
+        assert_eq!(body["valid"], false);
+
+        let target = format!("/best?position={}", ROOT.replace(' ', "+"));
+        let (status, body) = get(address, "GET", &target);
+        assert_eq!(status, 200);
+        let variant = Variant::new(3, 3).unwrap();
+        let root = notation::play_moves(&variant, &notation::parse_moves(ROOT, 3).unwrap());
+        let root = root.unwrap();
+        let result = Searcher::new(&variant, 16).solve(&root, None);
+        assert_eq!(body["winner"], result.winner(&root).unwrap());
+        let peg = body["best"].as_u64().unwrap() as usize - 1;
+        let mut next = root;
+        let won = variant.play(&mut next, peg).unwrap();
+        if !won {
+            let reply = Searcher::new(&variant, 16).solve(&next, None);
+            assert_eq!(reply.winner(&next), result.winner(&root));
+        }
+
+        let (status, body) = get(address, "GET", "/render?position=5+1");
+        assert_eq!(status, 200);
+        assert!(body["board"].as_str().unwrap().contains('X'));
+    }
+
+    #[test]
+    fn bad_requests_get_an_error_status() {
+        let address = start();
+        let (status, body) = get(address, "POST", "/best");
+        assert_eq!(status, 405);
+        assert!(body["error"].as_str().unwrap().contains("POST"));
+        let (status, _) = get(address, "GET", "/best?position=%zz");
+        assert_eq!(status, 400);
+        let (status, _) = get(address, "GET", "/best?position=5%2");
+        assert_eq!(status, 400);
+        let (status, _) = get(address, "GET", "/nowhere");
+        assert_eq!(status, 404);
+    }
+}
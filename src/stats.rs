@@ -0,0 +1,251 @@
+use std::fs::File;
+use std::io;
+
+use rayon::prelude::*;
+use serde::Serialize;
+
+use crate::database::{Database, Record};
+use crate::number_to_board;
+use crate::variant::{LineKind, Position, Variant};
+
+const BATCH_SIZE: usize = 1 << 20;
+
+#[derive(Clone, Default, Serialize)]
+#[cfg_attr(
+    feature = "python",
+    pyo3::pyclass(get_all, frozen, module = "connect3")
+)]
+pub struct DepthStats {
+    pub pieces: usize,
+    pub positions: u64,
+    /// Won positions in the database, by who completed the line
+    pub player_one_wins: u64,
+    pub player_two_wins: u64,
+    pub full_board_draws: u64,
+    /// Legal moves summed over the unfinished positions
+    pub moves: u64,
+    pub branching_factor: f64,
+    /// Perfect play results from the side to move's point of view
+    pub to_move_wins: u64,
+    pub to_move_draws: u64,
+    pub to_move_losses: u64,
+}
+
+#[derive(Clone, Default, Serialize)]
+#[cfg_attr(
+    feature = "python",
+    pyo3::pyclass(get_all, frozen, module = "connect3")
+)]
+pub struct Endings {
+    pub axis: u64,
+    pub face_diagonal: u64,
+    pub space_diagonal: u64,
+}
+
+#[derive(Clone, Serialize)]
+#[cfg_attr(
+    feature = "python",
+    pyo3::pyclass(get_all, frozen, module = "connect3")
+)]
+pub struct Stats {
+    pub positions: u64,
+    pub first_player_winning_positions: u64,
+    /// Kinds of line completed in each won position of the database, not in
+    /// each game played; a position with two kinds of line counts towards both
+    pub endings: Endings,
+    pub depths: Vec<DepthStats>,
+}
+
+impl Stats {
+    fn new() -> Stats {
+        Stats {
+            positions: 0,
+            first_player_winning_positions: 0,
+            endings: Endings::default(),
+            depths: (0..=27)
+                .map(|pieces| DepthStats {
+                    pieces,
+                    ..DepthStats::default()
+                })
+                .collect(),
+        }
+    }
+
+    fn add(&mut self, variant: &Variant, record: &Record) {
+        let g = number_to_board(record.state);
+        let position = Position::from_game(&g);
+        let depth = &mut self.depths[position.moves as usize];
+        self.positions += 1;
+        depth.positions += 1;
+        if record.winner == 1 {
+            self.first_player_winning_positions += 1;
+        }
+        if record.winner == 0 {
+            depth.to_move_draws += 1;
+        } else if record.winner == position.player {
+            depth.to_move_wins += 1;
+        } else {
+            depth.to_move_losses += 1;
+        }
+
+        match variant.winner(&position) {
+            Some(winner) => {
+                if winner == 1 {
+                    depth.player_one_wins += 1;
+                } else {
+                    depth.player_two_wins += 1;
+                }
+                let mut ended = [false; 3];
+                for (_, kind) in variant.completed_lines(position.pieces[(winner - 1) as usize]) {
+                    ended[kind as usize] = true;
+                }
+                self.endings.axis += ended[LineKind::Axis as usize] as u64;
+                self.endings.face_diagonal += ended[LineKind::FaceDiagonal as usize] as u64;
+                self.endings.space_diagonal += ended[LineKind::SpaceDiagonal as usize] as u64;
+            }
+            None if variant.is_full(&position) => depth.full_board_draws += 1,
+            None => {
+                depth.moves += (0..variant.pegs())
+                    .filter(|&peg| variant.can_play(&position, peg))
+                    .count() as u64;
+            }
+        }
+    }
+
+    fn merge(mut self, other: Stats) -> Stats {
+        self.positions += other.positions;
+        self.first_player_winning_positions += other.first_player_winning_positions;
+        self.endings.axis += other.endings.axis;
+        self.endings.face_diagonal += other.endings.face_diagonal;
+        self.endings.space_diagonal += other.endings.space_diagonal;
+        for (depth, other) in self.depths.iter_mut().zip(other.depths) {
+            depth.positions += other.positions;
+            depth.player_one_wins += other.player_one_wins;
+            depth.player_two_wins += other.player_two_wins;
+            depth.full_board_draws += other.full_board_draws;
+            depth.moves += other.moves;
+            depth.to_move_wins += other.to_move_wins;
+            depth.to_move_draws += other.to_move_draws;
+            depth.to_move_losses += other.to_move_losses;
+        }
+        self
+    }
+
+    fn finish(&mut self) {
+        for depth in self.depths.iter_mut() {
+            let open = depth.positions
+                - depth.player_one_wins
+                - depth.player_two_wins
+                - depth.full_board_draws;
+            if open > 0 {
+                depth.branching_factor = depth.moves as f64 / open as f64;
+            }
+        }
+    }
+
+    pub fn print_table(&self) {
+        println!(
+            "{:>6} {:>12} {:>11} {:>11} {:>9} {:>9} {:>11} {:>11} {:>11}",
+            "Pieces",
+            "Positions",
+            "P1 lines",
+            "P2 lines",
+            "Draws",
+            "Branching",
+            "To move W",
+            "To move D",
+            "To move L"
+        );
+        for depth in &self.depths {
+            println!(
+                "{:>6} {:>12} {:>11} {:>11} {:>9} {:>9.3} {:>11} {:>11} {:>11}",
+                depth.pieces,
+                depth.positions,
+                depth.player_one_wins,
+                depth.player_two_wins,
+                depth.full_board_draws,
+                depth.branching_factor,
+                depth.to_move_wins,
+                depth.to_move_draws,
+                depth.to_move_losses
+            );
+        }
+        println!("Total positions: {}", self.positions);
+        println!(
+            "Positions player 1 wins under perfect play: {}",
+            self.first_player_winning_positions
+        );
+        println!(
+            "Won positions with an axis line: {}, face diagonal: {}, space diagonal: {}",
+            self.endings.axis, self.endings.face_diagonal, self.endings.space_diagonal
+        );
+    }
+}
+
+/// Tallies every record of the database, calling `progress` with the records
+/// read and the total every ten million records.
+pub fn collect(database: &Database, mut progress: impl FnMut(u64, u64)) -> io::Result<Stats> {
+    let variant = Variant::new(3, 3).unwrap();
+    let mut records = database.records()?;
+    let mut stats = Stats::new();
+    let mut read: u64 = 0;
+    loop {
+        let batch: Vec<Record> = records
+            .by_ref()
+            .take(BATCH_SIZE)
+            .collect::<io::Result<Vec<Record>>>()
+            .map_err(|error| {
+                io::Error::new(
+                    error.kind(),
+                    format!("Read failed after {} records: {}", read, error),
+                )
+            })?;
+        if batch.is_empty() {
+            break;
+        }
+        read += batch.len() as u64;
+        let counted = batch
+            .par_iter()
+            .fold(Stats::new, |mut stats, record| {
+                stats.add(&variant, record);
+                stats
+            })
+            .reduce(Stats::new, Stats::merge);
+        stats = stats.merge(counted);
+        if read % 10000000 < BATCH_SIZE as u64 {
+            progress(read, database.len());
+        }
+    }
+    stats.finish();
+    Ok(stats)
+}
+
+/// `stats [--database PATH] [--json FILE]`
+pub fn stats(path: &str, json_path: Option<&str>) {
+    let database = match Database::open(path) {
+        Ok(database) => database,
+        Err(error) => {
+            println!("Could not open {}: {}", path, error);
+            return;
+        }
+    };
+    let stats = match collect(&database, |read, total| println!("{} / {}", read, total)) {
+        Ok(stats) => stats,
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    stats.print_table();
+
+    if let Some(json_path) = json_path {
+        match File::create(json_path)
+            .map_err(|error| error.to_string())
+            .and_then(|file| {
+                serde_json::to_writer_pretty(file, &stats).map_err(|error| error.to_string())
+            }) {
+            Ok(()) => println!("Written to {}", json_path),
+            Err(error) => println!("Could not write {}: {}", json_path, error),
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Write};
+
+use crate::database::{self, Database};
+use crate::notation::{self, format_peg};
+use crate::number_to_board;
+use crate::variant::{Position, Symmetry, Variant};
+
+/// A move for every position the attacker can reach while following it, keyed by
+/// state number. With `symmetric` set only the smallest state number among a
+/// position's symmetric copies is stored, and the move is in that copy's frame.
+pub struct Strategy {
+    root: u64,
+    symmetric: bool,
+    moves: BTreeMap<u64, u8>,
+}
+
+impl Strategy {
+    fn key<'a>(&self, symmetries: &'a [Symmetry], position: &Position) -> (u64, &'a Symmetry) {
+        if !self.symmetric {
+            return (position.state_number(), &symmetries[0]);
+        }
+        symmetries
+            .iter()
+            .map(|symmetry| (symmetry.apply(position).state_number(), symmetry))
+            .min_by_key(|(key, _)| *key)
+            .unwrap()
+    }
+
+    pub fn move_for(&self, symmetries: &[Symmetry], position: &Position) -> Option<usize> {
+        let (key, symmetry) = self.key(symmetries, position);
+        self.moves
+            .get(&key)
+            .map(|&peg| symmetry.unmap_peg(peg as usize))
+    }
+
+    pub fn len(&self) -> usize {
+        self.moves.len()
+    }
+
+    pub fn write(&self, path: &str) -> std::io::Result<()> {
+        let mut output = File::create(path)?;
+        writeln!(
+            output,
+            "# Winning strategy: one line per position with the winner to move, holding"
+        )?;
+        writeln!(
+            output,
+            "# its state number and the peg to play (1 + Down * 3 + Right)."
+        )?;
+        writeln!(
+            output,
+            "strategy state {}{}",
+            self.root,
+            if self.symmetric { " symmetric" } else { "" }
+        )?;
+        for (state_num, peg) in &self.moves {
+            writeln!(output, "{} {}", state_num, format_peg(*peg as usize))?;
+        }
+        Ok(())
+    }
+
+    pub fn read(path: &str) -> Result<Strategy, String> {
+        let file =
+            File::open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+        let mut lines = BufReader::new(file)
+            .lines()
+            .map_while(Result::ok)
+            .filter(|line| !line.trim().is_empty() && !line.starts_with('#'));
+        let header = lines.next().ok_or("Empty strategy file")?;
+        let words: Vec<&str> = header.split_whitespace().collect();
+        let root = match words[..] {
+            ["strategy", "state", root, ..] => root,
+            _ => return Err(format!("Bad header: {}", header)),
+        };
+        // Only games still in play have a move to follow
+        let root = notation::parse_unfinished_state(&Variant::new(3, 3).unwrap(), root)?;
+        let root = root.state_number();
+        let mut strategy = Strategy {
+            root,
+            symmetric: words.get(3) == Some(&"symmetric"),
+            moves: BTreeMap::new(),
+        };
+        for line in lines {
+            // Pegs are written counted from 1
+            let parsed = line.split_once(' ').and_then(|(state, peg)| {
+                Some((
+                    state.parse().ok()?,
+                    peg.trim().parse::<u8>().ok()?.checked_sub(1)?,
+                ))
+            });
+            match parsed {
+                Some((state_num, peg)) if peg < 9 => {
+                    strategy.moves.insert(state_num, peg);
+                }
+                _ => return Err(format!("Bad line: {}", line)),
+            }
+        }
+        Ok(strategy)
+    }
+}
+
+struct Extractor<'a> {
+    variant: &'a Variant,
+    symmetries: &'a [Symmetry],
+    database: &'a Database,
+    strategy: Strategy,
+}
+
+impl Extractor<'_> {
+    fn winner_of(&self, position: &Position) -> Result<i8, String> {
+        let state_num = position.state_number();
+        match self.database.lookup(state_num) {
+            Ok(Some(record)) => Ok(record.winner),
+            Ok(None) => Err(format!("State {} is not in the database", state_num)),
+            Err(error) => Err(format!("Could not read the database: {}", error)),
+        }
+    }
+
+    /// A winning move, preferring one that wins now and then one that leaves the
+    /// most replies with an immediate win, which keeps the strategy small.
+    fn choose(&self, position: &Position) -> Result<usize, String> {
+        let mut best: Option<(usize, usize)> = None;
+        for &peg in self.variant.peg_order() {
+            let mut after = *position;
+            match self.variant.play(&mut after, peg) {
+                None => continue,
+                Some(true) => return Ok(peg),
+                Some(false) => {}
+            }
+            if self.variant.is_full(&after) || self.winner_of(&after)? != position.player {
+                continue;
+            }
+            let finishing = (0..self.variant.pegs())
+                .filter(|&reply| {
+                    let mut next = after;
+                    self.variant.play(&mut next, reply) == Some(false)
+                        && (0..self.variant.pegs()).any(|answer| {
+                            let mut last = next;
+                            self.variant.play(&mut last, answer) == Some(true)
+                        })
+                })
+                .count();
+            if best.is_none_or(|(_, most)| finishing > most) {
+                best = Some((peg, finishing));
+            }
+        }
+        best.map(|(peg, _)| peg).ok_or(format!(
+            "State {} is not a win for player {} in the database",
+            position.state_number(),
+            position.player
+        ))
+    }
+
+    fn extract(&mut self, position: Position) -> Result<(), String> {
+        let (key, symmetry) = self.strategy.key(self.symmetries, &position);
+        if self.strategy.moves.contains_key(&key) {
+            return Ok(());
+        }
+        let peg = self.choose(&position)?;
+        self.strategy.moves.insert(key, symmetry.pegs[peg] as u8);
+        let mut after = position;
+        if self.variant.play(&mut after, peg) == Some(true) {
+            return Ok(());
+        }
+        for reply in 0..self.variant.pegs() {
+            let mut next = after;
+            match self.variant.play(&mut next, reply) {
+                None => {}
+                Some(false) if !self.variant.is_full(&next) => self.extract(next)?,
+                Some(_) => {
+                    return Err(format!(
+                        "Reply {} to state {} escapes the database's winning move",
+                        format_peg(reply),
+                        position.state_number()
+                    ))
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Follows the database's winning moves from `root` against every reply.
+pub fn extract_strategy(
+    database: &Database,
+    root: u64,
+    symmetric: bool,
+) -> Result<Strategy, String> {
+    let variant = Variant::new(3, 3).unwrap();
+    let symmetries = variant.symmetries();
+    let mut extractor = Extractor {
+        variant: &variant,
+        symmetries: &symmetries,
+        database,
+        strategy: Strategy {
+            root,
+            symmetric,
+            moves: BTreeMap::new(),
+        },
+    };
+    extractor.extract(Position::from_game(&number_to_board(root)))?;
+    Ok(extractor.strategy)
+}
+
+/// Plays the strategy against every reply from its root, without the database.
+/// Returns how many of its entries were reached.
+pub fn check_strategy(strategy: &Strategy) -> Result<usize, String> {
+    let variant = Variant::new(3, 3).unwrap();
+    let symmetries = variant.symmetries();
+    let mut checked: HashSet<u64> = HashSet::new();
+    let mut reached: HashSet<u64> = HashSet::new();
+    let mut stack = vec![Position::from_game(&number_to_board(strategy.root))];
+    while let Some(position) = stack.pop() {
+        if !checked.insert(position.state_number()) {
+            continue;
+        }
+        reached.insert(strategy.key(&symmetries, &position).0);
+        let peg = strategy
+            .move_for(&symmetries, &position)
+            .ok_or(format!("No move for state {}", position.state_number()))?;
+        let mut after = position;
+        match variant.play(&mut after, peg) {
+            None => {
+                return Err(format!(
+                    "State {}: peg {} is full",
+                    position.state_number(),
+                    format_peg(peg)
+                ))
+            }
+            Some(true) => continue,
+            Some(false) => {}
+        }
+        for reply in 0..variant.pegs() {
+            let mut next = after;
+            match variant.play(&mut next, reply) {
+                None => {}
+                Some(false) if !variant.is_full(&next) => stack.push(next),
+                Some(_) => {
+                    return Err(format!(
+                        "State {}: reply {} to peg {} is not a loss",
+                        position.state_number(),
+                        format_peg(reply),
+                        format_peg(peg)
+                    ))
+                }
+            }
+        }
+    }
+    Ok(reached.len())
+}
+
+/// `strategy [state] [--database PATH] [--output FILE] [--symmetric]`
+/// or `strategy --check FILE`
+pub fn strategy(args: &[String]) {
+    if let Some(path) = crate::flag_value(args, "--check") {
+        let result = Strategy::read(path).and_then(|strategy| {
+            check_strategy(&strategy).map(|reached| (strategy.len(), reached))
+        });
+        match result {
+            Ok((entries, reached)) => {
+                println!("Complete winning strategy, {} entries", entries);
+                if reached < entries {
+                    println!("{} entries are never reached", entries - reached);
+                }
+            }
+            Err(error) => {
+                println!("Invalid strategy: {}", error);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+    let root = match crate::notation::position_arg(args, &Variant::new(3, 3).unwrap()) {
+        Ok(root) => root.state_number(),
+        Err(error) => {
+            println!("{}", error);
+            return;
+        }
+    };
+    let path = crate::flag_value(args, "--database").unwrap_or(database::DEFAULT_PATH);
+    let database = match Database::open(path) {
+        Ok(database) => database,
+        Err(error) => {
+            println!("Could not open {}: {}", path, error);
+            return;
+        }
+    };
+    let symmetric = crate::has_switch(args, "--symmetric");
+    match extract_strategy(&database, root, symmetric) {
+        Ok(strategy) => {
+            let output = crate::flag_value(args, "--output").unwrap_or("strategy.txt");
+            strategy.write(output).unwrap();
+            println!("{} positions written to {}", strategy.len(), output);
+        }
+        Err(error) => println!("{}", error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{temp_path, write_database};
+
+    #[test]
+    fn exported_strategy_replays_against_the_rules() {
+        let variant = Variant::new(3, 3).unwrap();
+        let pegs = notation::parse_moves("5 8 8 3 6 2 3 4 7 7", 3).unwrap();
+        let root = notation::play_moves(&variant, &pegs).unwrap();
+        let database_path = temp_path("strategy.bin");
+        let strategy_path = temp_path("strategy.txt");
+        write_database(&variant, root, &database_path);
+        let database = Database::open(&database_path).unwrap();
+
+        for symmetric in [false, true] {
+            let strategy = extract_strategy(&database, root.state_number(), symmetric).unwrap();
+            assert_eq!(check_strategy(&strategy), Ok(strategy.len()));
+            strategy.write(&strategy_path).unwrap();
+            let read = Strategy::read(&strategy_path).unwrap();
+            assert_eq!(read.moves, strategy.moves);
+            assert_eq!(check_strategy(&read), Ok(strategy.len()));
+        }
+        std::fs::remove_file(database_path).unwrap();
+        std::fs::remove_file(strategy_path).unwrap();
+    }
+
+    #[test]
+    fn check_rejects_missing_answers() {
+        let variant = Variant::new(3, 3).unwrap();
+        let mut strategy = Strategy {
+            root: 0,
+            symmetric: false,
+            moves: BTreeMap::new(),
+        };
+        // Player 1 starts in a corner and has no answer stored for any reply
+        strategy.moves.insert(variant.start().state_number(), 0);
+        assert!(check_strategy(&strategy).is_err());
+    }
+
+    #[test]
+    fn read_rejects_roots_no_game_is_in() {
+        let won = notation::parse_game("1 5 2 9 3").unwrap();
+        let path = temp_path("bad-root.txt");
+        for root in [
+            "7625597484987".to_string(),
+            "4".to_string(),
+            crate::game_to_number(won).to_string(),
+        ] {
+            std::fs::write(&path, format!("strategy state {}\n{} 5\n", root, root)).unwrap();
+            assert!(Strategy::read(&path).is_err(), "state {}", root);
+        }
+        std::fs::remove_file(path).unwrap();
+    }
+}
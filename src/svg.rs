@@ -0,0 +1,241 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::play::{self, Engine};
+use crate::variant::Variant;
+use crate::{convert, notation, render};
+use crate::{is_full, is_over, number_to_board, switch_player, Game};
+
+const PEG_WIDTH: usize = 100;
+const PEG_HEIGHT: usize = 120;
+const MARGIN: usize = 10;
+const TITLE_HEIGHT: usize = 30;
+const DIAGRAM_WIDTH: usize = 3 * PEG_WIDTH + 2 * MARGIN;
+const DIAGRAM_HEIGHT: usize = TITLE_HEIGHT + 3 * PEG_HEIGHT + MARGIN;
+
+const PLAYER_COLOURS: [&str; 2] = ["#d62728", "#1f77b4"];
+const WIN_COLOUR: &str = "#2ca02c";
+const DRAW_COLOUR: &str = "#999999";
+const LOSS_COLOUR: &str = "#d62728";
+
+/// One board drawn as nine pegs seen from the side, in the same layout as the
+/// squares a1 to c3.
+pub struct Diagram {
+    pub g: Game,
+    pub title: String,
+    /// Move numbers of the pieces, keyed by `(peg, height)`
+    pub numbers: HashMap<(usize, usize), usize>,
+    /// The winner under perfect play after each legal peg, shaded from the
+    /// point of view of the side to move
+    pub outcomes: Vec<(usize, i8)>,
+}
+
+impl Diagram {
+    fn draw(&self, output: &mut String, left: usize, top: usize) {
+        let line = render::winning_cells(&self.g);
+        writeln!(output, r#"<g transform="translate({},{})">"#, left, top).unwrap();
+        writeln!(
+            output,
+            r#"<text x="{}" y="20" font-size="14">{}</text>"#,
+            MARGIN, self.title
+        )
+        .unwrap();
+        for peg in 0..9 {
+            let centre = MARGIN + (peg % 3) * PEG_WIDTH + PEG_WIDTH / 2;
+            let cell_top = TITLE_HEIGHT + (peg / 3) * PEG_HEIGHT;
+            let base = cell_top + PEG_HEIGHT - 22;
+            if let Some(&(_, winner)) = self.outcomes.iter().find(|(p, _)| *p == peg) {
+                let (colour, letter) = if winner == 0 {
+                    (DRAW_COLOUR, "D")
+                } else if winner == self.g.player {
+                    (WIN_COLOUR, "W")
+                } else {
+                    (LOSS_COLOUR, "L")
+                };
+                writeln!(
+                    output,
+                    r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}" fill-opacity="0.2"/>"#,
+                    centre - PEG_WIDTH / 2 + 2,
+                    cell_top + 2,
+                    PEG_WIDTH - 4,
+                    PEG_HEIGHT - 4,
+                    colour
+                )
+                .unwrap();
+                writeln!(
+                    output,
+                    r#"<text x="{}" y="{}" font-size="14" fill="{}">{}</text>"#,
+                    centre + 30,
+                    cell_top + 18,
+                    colour,
+                    letter
+                )
+                .unwrap();
+            }
+            writeln!(
+                output,
+                r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#555555" stroke-width="4"/>"##,
+                centre - 40,
+                base,
+                centre + 40,
+                base
+            )
+            .unwrap();
+            writeln!(
+                output,
+                r##"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="#8c6d46" stroke-width="6"/>"##,
+                centre,
+                base,
+                centre,
+                base - 80
+            )
+            .unwrap();
+            for z in 0..3 {
+                let value = self.g.board.data[peg / 3][peg % 3][z];
+                if value == 0 {
+                    continue;
+                }
+                let middle = base - 12 - z * 24;
+                let (stroke, width) = if line.contains(&(peg, z)) {
+                    ("#ffbf00", 4)
+                } else {
+                    ("#333333", 1)
+                };
+                writeln!(
+                    output,
+                    r#"<ellipse cx="{}" cy="{}" rx="32" ry="11" fill="{}" stroke="{}" stroke-width="{}"/>"#,
+                    centre,
+                    middle,
+                    PLAYER_COLOURS[(value - 1) as usize],
+                    stroke,
+                    width
+                )
+                .unwrap();
+                if let Some(number) = self.numbers.get(&(peg, z)) {
+                    writeln!(
+                        output,
+                        r#"<text x="{}" y="{}" font-size="12" fill="white" text-anchor="middle">{}</text>"#,
+                        centre,
+                        middle + 4,
+                        number
+                    )
+                    .unwrap();
+                }
+            }
+            writeln!(
+                output,
+                r##"<text x="{}" y="{}" font-size="12" fill="#555555" text-anchor="middle">{}</text>"##,
+                centre,
+                base + 16,
+                notation::format_square(peg, 3)
+            )
+            .unwrap();
+        }
+        writeln!(output, "</g>").unwrap();
+    }
+}
+
+/// The diagrams in rows of `columns`, as a standalone SVG file.
+pub fn document(diagrams: &[Diagram], columns: usize) -> String {
+    let columns = columns.clamp(1, diagrams.len().max(1));
+    let rows = diagrams.len().div_ceil(columns);
+    let mut output = String::new();
+    writeln!(
+        output,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" font-family="sans-serif">"#,
+        columns * DIAGRAM_WIDTH,
+        rows * DIAGRAM_HEIGHT
+    )
+    .unwrap();
+    writeln!(output, r#"<rect width="100%" height="100%" fill="white"/>"#).unwrap();
+    for (index, diagram) in diagrams.iter().enumerate() {
+        diagram.draw(
+            &mut output,
+            (index % columns) * DIAGRAM_WIDTH,
+            (index / columns) * DIAGRAM_HEIGHT,
+        );
+    }
+    writeln!(output, "</svg>").unwrap();
+    output
+}
+
+fn title(g: &Game, moves: usize) -> String {
+    let state = if is_over(g) {
+        format!("player {} wins", switch_player(g.player))
+    } else if is_full(g) {
+        "draw".to_string()
+    } else {
+        format!("player {} to move", g.player)
+    };
+    format!("Move {}, {}", moves, state)
+}
+
+/// The board after the first `count` moves, numbering every piece.
+fn diagram_after(pegs: &[usize], count: usize) -> Diagram {
+    let variant = Variant::new(3, 3).unwrap();
+    let position = notation::play_moves(&variant, &pegs[..count]).unwrap();
+    let mut heights = [0; 9];
+    let mut numbers = HashMap::new();
+    for (index, &peg) in pegs[..count].iter().enumerate() {
+        numbers.insert((peg, heights[peg]), index + 1);
+        heights[peg] += 1;
+    }
+    let g = number_to_board(position.state_number());
+    Diagram {
+        title: title(&g, count),
+        g,
+        numbers,
+        outcomes: Vec::new(),
+    }
+}
+
+/// `svg [POSITION] [--output FILE] [--strip] [--columns N] [--outcomes]`
+/// `[--engine database|search] [--database PATH]`
+pub fn svg(args: &[String]) {
+    match write_svg(args) {
+        Ok((1, path)) => println!("Diagram written to {}", path),
+        Ok((diagrams, path)) => println!("{} diagrams written to {}", diagrams, path),
+        Err(error) => println!("{}", error),
+    }
+}
+
+fn write_svg(args: &[String]) -> Result<(usize, &str), String> {
+    let variant = Variant::new(3, 3).unwrap();
+    let strip = crate::has_switch(args, "--strip");
+    let text = crate::positional(args, 0).unwrap_or("");
+    // Only a move sequence says which piece went first, so only it gets numbers
+    let numbered = text.is_empty() || notation::is_move_sequence(text);
+    let pegs = if numbered {
+        let pegs = notation::parse_moves(text, 3)?;
+        notation::play_moves(&variant, &pegs)?;
+        pegs
+    } else if strip {
+        return Err("A strip needs the game as a move sequence".to_string());
+    } else {
+        convert::moves_to(&notation::parse_game(text)?)?
+    };
+
+    let first = if strip { 1.min(pegs.len()) } else { pegs.len() };
+    let mut diagrams: Vec<Diagram> = (first..=pegs.len())
+        .map(|count| diagram_after(&pegs, count))
+        .collect();
+    if !numbered {
+        diagrams[0].numbers.clear();
+    }
+    if crate::has_switch(args, "--outcomes") {
+        let mut engine: Engine = play::open_engine(args, &variant)?;
+        for diagram in diagrams.iter_mut() {
+            if !is_over(&diagram.g) && !is_full(&diagram.g) {
+                diagram.outcomes = engine.peg_results(&diagram.g)?;
+            }
+        }
+    }
+
+    let columns = crate::flag_value(args, "--columns")
+        .and_then(|columns| columns.parse().ok())
+        .unwrap_or(5);
+    let path = crate::flag_value(args, "--output").unwrap_or("board.svg");
+    std::fs::write(path, document(&diagrams, columns))
+        .map_err(|error| format!("Could not write {}: {}", path, error))?;
+    Ok((diagrams.len(), path))
+}
@@ -0,0 +1,64 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::negamax::Searcher;
+use crate::variant::{Position, Variant};
+
+/// A path in the temp directory that no other test, in this run or another,
+/// will share.
+pub fn temp_path(name: &str) -> String {
+    static NEXT: AtomicUsize = AtomicUsize::new(0);
+    let count = NEXT.fetch_add(1, Ordering::Relaxed);
+    std::env::temp_dir()
+        .join(format!(
+            "connect3-{}-{}-{}",
+            std::process::id(),
+            count,
+            name
+        ))
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Solves every position reachable from `root` with the search and writes them
+/// as a database file, storing the move the search picks. Finished games are
+/// written with no move, as the solver writes them.
+pub fn write_database(variant: &Variant, root: Position, path: &str) {
+    let mut searcher = Searcher::new(variant, 16);
+    let mut records: BTreeMap<u64, (i8, i8)> = BTreeMap::new();
+    let mut stack = vec![root];
+    while let Some(position) = stack.pop() {
+        let state_num = position.state_number();
+        if records.contains_key(&state_num) {
+            continue;
+        }
+        if let Some(winner) = variant.winner(&position) {
+            records.insert(state_num, (-1, winner));
+            continue;
+        }
+        if variant.is_full(&position) {
+            records.insert(state_num, (-1, 0));
+            continue;
+        }
+        let result = searcher.solve(&position, None);
+        let stored_move = result.best_peg.map_or(-1, |peg| {
+            variant.cell(peg, position.heights[peg] as usize) as i8
+        });
+        records.insert(state_num, (stored_move, result.winner(&position).unwrap()));
+        for peg in 0..variant.pegs() {
+            let mut next = position;
+            if variant.play(&mut next, peg).is_some() {
+                stack.push(next);
+            }
+        }
+    }
+    let mut output = File::create(path).unwrap();
+    for (state_num, (stored_move, winner)) in records {
+        output.write_all(&state_num.to_le_bytes()).unwrap();
+        output
+            .write_all(&[stored_move as u8, winner as u8])
+            .unwrap();
+    }
+}
@@ -0,0 +1,258 @@
+use std::fs;
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::player::{self, Player};
+use crate::record::GameRecord;
+use crate::variant::Variant;
+use crate::{is_full, is_over, place_new_piece, switch_player, Game};
+
+const DEFAULT_GAMES: usize = 100;
+const DEFAULT_OUTPUT: &str = "tournament";
+
+struct Finished {
+    /// Whether player A moved first
+    a_first: bool,
+    moves: Vec<usize>,
+    /// 1 or 2 for the player who completed a line, 0 for a draw
+    winner: i8,
+}
+
+impl Finished {
+    /// 1 for a win for A, 0.5 for a draw and 0 for a loss.
+    fn score(&self) -> f64 {
+        match self.winner {
+            0 => 0.5,
+            1 if self.a_first => 1.0,
+            2 if !self.a_first => 1.0,
+            _ => 0.0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct Tally {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+}
+
+impl Tally {
+    fn add(&mut self, score: f64) {
+        if score == 1.0 {
+            self.wins += 1;
+        } else if score == 0.0 {
+            self.losses += 1;
+        } else {
+            self.draws += 1;
+        }
+    }
+
+    fn games(&self) -> usize {
+        self.wins + self.draws + self.losses
+    }
+}
+
+/// Plays from the empty board until someone completes a line or the board fills.
+fn play_game(players: &mut [Box<dyn Player + '_>; 2]) -> Result<(Vec<usize>, i8), String> {
+    let mut g = Game {
+        board: crate::make_new_board(),
+        player: 1,
+    };
+    let mut moves = Vec::new();
+    loop {
+        if is_over(&g) {
+            return Ok((moves, switch_player(g.player)));
+        }
+        if is_full(&g) {
+            return Ok((moves, 0));
+        }
+        let player = &mut players[(g.player - 1) as usize];
+        let peg = player.choose(&g)?;
+        if place_new_piece(&mut g.board, peg / 3, peg % 3, g.player).is_none() {
+            return Err(format!("{} played on full peg {}", player.name(), peg + 1));
+        }
+        g.player = switch_player(g.player);
+        moves.push(peg);
+    }
+}
+
+/// The rating difference that expects `score` per game, infinite at 0 and 1.
+fn elo(score: f64) -> String {
+    if score <= 0.0 {
+        "-inf".to_string()
+    } else if score >= 1.0 {
+        "+inf".to_string()
+    } else {
+        let difference = (-400.0 * (1.0 / score - 1.0).log10()).round();
+        // Even scores come out as -0, and rounding can give -0 nearby
+        if difference == 0.0 {
+            "0".to_string()
+        } else {
+            format!("{:+.0}", difference)
+        }
+    }
+}
+
+fn print_summary(names: [&str; 2], games: &[Finished]) {
+    let mut total = Tally::default();
+    let mut as_first = Tally::default();
+    let mut as_second = Tally::default();
+    for game in games {
+        total.add(game.score());
+        if game.a_first {
+            as_first.add(game.score());
+        } else {
+            as_second.add(game.score());
+        }
+    }
+    println!("Results for A ({}) against B ({}):", names[0], names[1]);
+    for (label, tally) in [
+        ("Overall", &total),
+        ("A first", &as_first),
+        ("A second", &as_second),
+    ] {
+        println!(
+            "  {:<9} {} games, {} wins, {} draws, {} losses",
+            label,
+            tally.games(),
+            tally.wins,
+            tally.draws,
+            tally.losses
+        );
+    }
+    let plies: usize = games.iter().map(|game| game.moves.len()).sum();
+    println!(
+        "Average game length: {:.1} moves",
+        plies as f64 / games.len() as f64
+    );
+
+    let n = games.len() as f64;
+    let score = games.iter().map(Finished::score).sum::<f64>() / n;
+    println!("Score for A: {:.1}%", 100.0 * score);
+    if score <= 0.0 || score >= 1.0 {
+        println!("Elo difference: unbounded, one side scored every point");
+        return;
+    }
+    // A 95% interval from the spread of the per-game scores
+    let variance = games
+        .iter()
+        .map(|game| (game.score() - score).powi(2))
+        .sum::<f64>()
+        / n;
+    let margin = 1.96 * (variance / n).sqrt();
+    println!(
+        "Elo difference for A: {} (95% interval {} to {})",
+        elo(score),
+        elo(score - margin),
+        elo(score + margin)
+    );
+}
+
+fn run(args: &[String]) -> Result<(), String> {
+    let names = match (crate::positional(args, 0), crate::positional(args, 1)) {
+        (Some(a), Some(b)) => [a, b],
+        _ => return Err(format!("Give two players: {}", player::PLAYER_NAMES)),
+    };
+    if names.contains(&"human") {
+        return Err("Tournaments are between computer players".to_string());
+    }
+    let games = match crate::flag_value(args, "--games") {
+        Some(games) => games
+            .parse()
+            .map_err(|_| format!("Bad number of games: {}", games))?,
+        None => DEFAULT_GAMES,
+    };
+    if games == 0 {
+        return Err("Play at least one game".to_string());
+    }
+    let seed = player::seed(args)?;
+    let output = crate::flag_value(args, "--output").unwrap_or(DEFAULT_OUTPUT);
+    let variant = Variant::new(3, 3).unwrap();
+    let database = if names.contains(&"database") {
+        Some(player::open_database(args).map_err(|error| {
+            format!(
+                "{}\nPick search instead of database to play without it",
+                error
+            )
+        })?)
+    } else {
+        None
+    };
+    // Checks the names before any game starts
+    for name in names {
+        player::new_player(name, database.clone(), &variant, 0)?;
+    }
+    fs::create_dir_all(output)
+        .map_err(|error| format!("Could not create {}: {}", output, error))?;
+
+    println!(
+        "Playing {} games between {} and {}, seed {}",
+        games, names[0], names[1], seed
+    );
+    // Player A moves first in the even games. Each game seeds its own players
+    // so the results do not depend on how the games are spread over threads.
+    let results: Vec<Finished> = (0..games)
+        .into_par_iter()
+        .map(|index| -> Result<Finished, String> {
+            let a_first = index % 2 == 0;
+            let seats = if a_first { names } else { [names[1], names[0]] };
+            let game_seed = seed.wrapping_add(2 * index as u64);
+            let mut players = [
+                player::new_player(seats[0], database.clone(), &variant, game_seed)?,
+                player::new_player(
+                    seats[1],
+                    database.clone(),
+                    &variant,
+                    game_seed.wrapping_add(1),
+                )?,
+            ];
+            let (moves, winner) = play_game(&mut players)
+                .map_err(|error| format!("Game {}: {}", index + 1, error))?;
+
+            let mut record = GameRecord::new(&moves);
+            record.set_header("Player1", &players[0].name());
+            record.set_header("Player2", &players[1].name());
+            record.set_header("Round", &(index + 1).to_string());
+            let path = Path::new(output).join(format!("game-{:04}.txt", index + 1));
+            record
+                .write(&path.to_string_lossy())
+                .map_err(|error| format!("Could not write {}: {}", path.display(), error))?;
+            Ok(Finished {
+                a_first,
+                moves,
+                winner,
+            })
+        })
+        .collect::<Result<Vec<Finished>, String>>()?;
+
+    print_summary(names, &results);
+    println!("Game records written to {}", output);
+    Ok(())
+}
+
+/// `tournament PLAYER PLAYER [--games N] [--seed N] [--output DIR] [--database PATH]`
+///
+/// Players are random, greedy, database, search or mcts with an optional budget,
+/// like mcts:5000 or mcts:200ms:shared. They take turns to move first and the
+/// games run in parallel.
+pub fn tournament(args: &[String]) {
+    if let Err(error) = run(args) {
+        println!("{}", error);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn elo_has_no_negative_zero() {
+        assert_eq!(elo(0.5), "0");
+        assert_eq!(elo(0.4999), "0");
+        assert_eq!(elo(0.75), "+191");
+        assert_eq!(elo(0.25), "-191");
+        assert_eq!(elo(0.0), "-inf");
+    }
+}
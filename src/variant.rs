@@ -0,0 +1,358 @@
+use std::fmt;
+use std::str::FromStr;
+
+use crate::Game;
+
+/// The lines `is_over` checks: the three axes, one diagonal per face orientation
+/// and the four space diagonals. The other face diagonals (x + y = 2 and so on)
+/// are not checked there, and the solved database depends on that.
+const DIRECTIONS: [(i64, i64, i64); 10] = [
+    (1, 0, 0),
+    (0, 1, 0),
+    (0, 0, 1),
+    (0, 1, 1),
+    (1, 0, 1),
+    (1, 1, 0),
+    (1, 1, 1),
+    (1, -1, 1),
+    (-1, 1, 1),
+    (-1, -1, 1),
+];
+
+/// Moves peg `(x, y)` on a board whose last row and column are `n`.
+type PegMap = fn(usize, usize, usize) -> (usize, usize);
+
+/// Board size and line length for games on an n x n x n grid. The standard game is
+/// 3x3x3 connect 3; anything up to 4x4x4 fits in the 64 bit boards used here.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variant {
+    pub size: usize,
+    pub connect: usize,
+    lines: Vec<u64>,
+    kinds: Vec<LineKind>,
+    lines_through: Vec<Vec<u64>>,
+    peg_order: Vec<usize>,
+}
+
+impl Variant {
+    pub fn new(size: usize, connect: usize) -> Result<Variant, String> {
+        if !(2..=4).contains(&size) {
+            return Err(format!("board size must be 2 to 4, not {}", size));
+        }
+        if !(2..=size).contains(&connect) {
+            return Err(format!(
+                "line length must be 2 to {}, not {}",
+                size, connect
+            ));
+        }
+        let cells = size * size * size;
+        let mut lines: Vec<u64> = Vec::new();
+        let mut kinds: Vec<LineKind> = Vec::new();
+        let n = size as i64;
+        for x in 0..n {
+            for y in 0..n {
+                for z in 0..n {
+                    for &(dx, dy, dz) in &DIRECTIONS {
+                        let end = connect as i64 - 1;
+                        let (ex, ey, ez) = (x + dx * end, y + dy * end, z + dz * end);
+                        if !(0..n).contains(&ex) || !(0..n).contains(&ey) || !(0..n).contains(&ez) {
+                            continue;
+                        }
+                        let mut line: u64 = 0;
+                        for step in 0..connect as i64 {
+                            let cell =
+                                (x + dx * step) + n * (y + dy * step) + n * n * (z + dz * step);
+                            line |= 1 << cell;
+                        }
+                        lines.push(line);
+                        kinds.push(match dx.abs() + dy.abs() + dz.abs() {
+                            1 => LineKind::Axis,
+                            2 => LineKind::FaceDiagonal,
+                            _ => LineKind::SpaceDiagonal,
+                        });
+                    }
+                }
+            }
+        }
+        let lines_through: Vec<Vec<u64>> = (0..cells)
+            .map(|cell| {
+                lines
+                    .iter()
+                    .copied()
+                    .filter(|line| line & (1 << cell) != 0)
+                    .collect()
+            })
+            .collect();
+        // Pegs with the most lines through them first, which puts the centre first
+        let mut peg_order: Vec<usize> = (0..size * size).collect();
+        let peg_lines = |peg: usize| -> usize {
+            (0..size)
+                .map(|z| lines_through[peg / size + size * (peg % size) + size * size * z].len())
+                .sum()
+        };
+        peg_order.sort_by_key(|&peg| std::cmp::Reverse(peg_lines(peg)));
+        Ok(Variant {
+            size,
+            connect,
+            lines,
+            kinds,
+            lines_through,
+            peg_order,
+        })
+    }
+
+    pub fn is_standard(&self) -> bool {
+        self.size == 3 && self.connect == 3
+    }
+
+    pub fn pegs(&self) -> usize {
+        self.size * self.size
+    }
+
+    pub fn cells(&self) -> usize {
+        self.size * self.size * self.size
+    }
+
+    /// Pegs in the order searches should try them.
+    pub fn peg_order(&self) -> &[usize] {
+        &self.peg_order
+    }
+
+    /// Bit for the piece at height `z` on `peg`, using the `x + 3y + 9z` layout
+    /// of the state numbers. Pegs are numbered `Down * size + Right`.
+    pub fn cell(&self, peg: usize, z: usize) -> usize {
+        peg / self.size + self.size * (peg % self.size) + self.size * self.size * z
+    }
+
+    /// The peg holding bit `cell`, undoing `cell`.
+    pub fn peg_of(&self, cell: usize) -> usize {
+        cell % self.size * self.size + cell / self.size % self.size
+    }
+
+    pub fn start(&self) -> Position {
+        Position {
+            pieces: [0, 0],
+            heights: [0; 16],
+            player: 1,
+            moves: 0,
+        }
+    }
+
+    /// Every line as a mask of its cells.
+    pub fn lines(&self) -> &[u64] {
+        &self.lines
+    }
+
+    pub fn lines_through(&self, cell: usize) -> &[u64] {
+        &self.lines_through[cell]
+    }
+
+    pub fn has_line(&self, pieces: u64) -> bool {
+        self.lines.iter().any(|&line| line & !pieces == 0)
+    }
+
+    /// Drops a piece for the player to move. Returns whether it completed a line,
+    /// or None if the peg is full.
+    pub fn play(&self, position: &mut Position, peg: usize) -> Option<bool> {
+        let z = position.heights[peg] as usize;
+        if z >= self.size {
+            return None;
+        }
+        let cell = self.cell(peg, z);
+        let mine = &mut position.pieces[(position.player - 1) as usize];
+        *mine |= 1 << cell;
+        let won = self.lines_through[cell]
+            .iter()
+            .any(|&line| line & !*mine == 0);
+        position.heights[peg] += 1;
+        position.moves += 1;
+        position.player = if position.player == 1 { 2 } else { 1 };
+        Some(won)
+    }
+
+    pub fn can_play(&self, position: &Position, peg: usize) -> bool {
+        (position.heights[peg] as usize) < self.size
+    }
+
+    pub fn is_full(&self, position: &Position) -> bool {
+        position.moves as usize == self.cells()
+    }
+
+    /// The player who has completed a line, if any.
+    pub fn winner(&self, position: &Position) -> Option<i8> {
+        (1..=2).find(|&player| self.has_line(position.pieces[(player - 1) as usize]))
+    }
+
+    /// Every completed line in `pieces` along with its direction.
+    pub fn completed_lines(&self, pieces: u64) -> impl Iterator<Item = (u64, LineKind)> + '_ {
+        self.lines
+            .iter()
+            .zip(&self.kinds)
+            .filter(move |(&line, _)| line & !pieces == 0)
+            .map(|(&line, &kind)| (line, kind))
+    }
+
+    /// Reflections and rotations of the pegs that keep the same set of lines.
+    /// Always starts with the identity.
+    pub fn symmetries(&self) -> Vec<Symmetry> {
+        let n = self.size - 1;
+        let maps: [PegMap; 8] = [
+            |_, x, y| (x, y),
+            |n, x, y| (n - y, x),
+            |n, x, y| (n - x, n - y),
+            |n, x, y| (y, n - x),
+            |_, x, y| (y, x),
+            |n, x, y| (n - x, y),
+            |n, x, y| (x, n - y),
+            |n, x, y| (n - y, n - x),
+        ];
+        let mut sorted_lines = self.lines.clone();
+        sorted_lines.sort_unstable();
+        let mut symmetries = Vec::new();
+        for map in maps {
+            let pegs: Vec<usize> = (0..self.pegs())
+                .map(|peg| {
+                    let (x, y) = map(n, peg / self.size, peg % self.size);
+                    x * self.size + y
+                })
+                .collect();
+            let mut cells = vec![0; self.cells()];
+            for (peg, &to) in pegs.iter().enumerate() {
+                for z in 0..self.size {
+                    cells[self.cell(peg, z)] = self.cell(to, z);
+                }
+            }
+            let symmetry = Symmetry { pegs, cells };
+            let mut moved: Vec<u64> = self
+                .lines
+                .iter()
+                .map(|&line| symmetry.map_bits(line))
+                .collect();
+            moved.sort_unstable();
+            if moved == sorted_lines {
+                symmetries.push(symmetry);
+            }
+        }
+        symmetries
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LineKind {
+    Axis,
+    FaceDiagonal,
+    SpaceDiagonal,
+}
+
+/// A peg permutation along with the cell permutation it causes.
+pub struct Symmetry {
+    pub pegs: Vec<usize>,
+    cells: Vec<usize>,
+}
+
+impl Symmetry {
+    fn map_bits(&self, bits: u64) -> u64 {
+        let mut moved = 0;
+        for (cell, &to) in self.cells.iter().enumerate() {
+            if bits & (1 << cell) != 0 {
+                moved |= 1 << to;
+            }
+        }
+        moved
+    }
+
+    pub fn apply(&self, position: &Position) -> Position {
+        let mut moved = *position;
+        moved.pieces = [
+            self.map_bits(position.pieces[0]),
+            self.map_bits(position.pieces[1]),
+        ];
+        for (peg, &to) in self.pegs.iter().enumerate() {
+            moved.heights[to] = position.heights[peg];
+        }
+        moved
+    }
+
+    /// The peg that `peg` came from.
+    pub fn unmap_peg(&self, peg: usize) -> usize {
+        self.pegs.iter().position(|&to| to == peg).unwrap()
+    }
+}
+
+/// Reads the form `Display` writes, like "3x3x3 connect 3".
+impl FromStr for Variant {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Variant, String> {
+        let error = || format!("Bad variant: {}", text);
+        let words: Vec<&str> = text.split_whitespace().collect();
+        let (dimensions, connect) = match words[..] {
+            [dimensions, "connect", connect] => (dimensions, connect),
+            _ => return Err(error()),
+        };
+        let sizes: Vec<usize> = dimensions
+            .split('x')
+            .map(|size| size.parse().map_err(|_| error()))
+            .collect::<Result<_, _>>()?;
+        if sizes.len() != 3 || sizes.iter().any(|&size| size != sizes[0]) {
+            return Err(error());
+        }
+        Variant::new(sizes[0], connect.parse().map_err(|_| error())?)
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}x{} connect {}",
+            self.size, self.size, self.size, self.connect
+        )
+    }
+}
+
+/// A position in any variant as one bitboard per player.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Position {
+    pub pieces: [u64; 2],
+    pub heights: [u8; 16],
+    pub player: i8,
+    pub moves: u8,
+}
+
+impl Position {
+    pub fn from_game(g: &Game) -> Position {
+        let mut position = Position {
+            pieces: [0, 0],
+            heights: [0; 16],
+            player: g.player,
+            moves: 0,
+        };
+        for x in 0..3 {
+            for y in 0..3 {
+                for z in 0..3 {
+                    let value = g.board.data[x][y][z];
+                    if value != 0 {
+                        position.pieces[(value - 1) as usize] |= 1 << (x + 3 * y + 9 * z);
+                        position.heights[x * 3 + y] += 1;
+                        position.moves += 1;
+                    }
+                }
+            }
+        }
+        position
+    }
+
+    /// The base 3 state number of a 3x3x3 position.
+    pub fn state_number(&self) -> u64 {
+        let mut output: u64 = 0;
+        for cell in 0..27 {
+            if self.pieces[0] & (1 << cell) != 0 {
+                output += 3_u64.pow(cell);
+            } else if self.pieces[1] & (1 << cell) != 0 {
+                output += 2 * 3_u64.pow(cell);
+            }
+        }
+        output
+    }
+}
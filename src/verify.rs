@@ -0,0 +1,391 @@
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+use rayon::prelude::*;
+
+use crate::database::{Database, Record, RECORD_SIZE};
+use crate::{
+    game_to_number, get_all_next_numbers, is_full, is_over, number_to_board,
+    stored_move_to_next_number, switch_player, Game,
+};
+
+const MAX_EXAMPLES: usize = 20;
+const CHUNK_SIZE: usize = 1 << 16;
+/// Records per block of the in-memory index
+const BLOCK: u64 = 64;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Problem {
+    Unsorted,
+    InvalidState,
+    Unreachable,
+    BadWinner,
+    TerminalValue,
+    TerminalMove,
+    MissingChild,
+    WrongValue,
+    BadMove,
+    MoveValue,
+    MissingRoot,
+}
+
+impl Problem {
+    fn describe(self) -> &'static str {
+        match self {
+            Problem::Unsorted => "key not greater than the previous key",
+            Problem::InvalidState => "not a legal board encoding",
+            Problem::Unreachable => "play continued after a win",
+            Problem::BadWinner => "winner outside 0..=2",
+            Problem::TerminalValue => "finished game with the wrong winner",
+            Problem::TerminalMove => "finished game with a stored move",
+            Problem::MissingChild => "a next state is missing from the file",
+            Problem::WrongValue => "winner is not the minimax of the next states",
+            Problem::BadMove => "stored move is not a legal move",
+            Problem::MoveValue => "stored move leads to a state with a different winner",
+            Problem::MissingRoot => "the starting position is missing",
+        }
+    }
+}
+
+#[derive(Default)]
+struct Report {
+    counts: BTreeMap<Problem, u64>,
+    examples: Vec<(u64, Problem)>,
+}
+
+impl Report {
+    fn add(&mut self, state_num: u64, problem: Problem) {
+        *self.counts.entry(problem).or_insert(0) += 1;
+        if self.examples.len() < MAX_EXAMPLES {
+            self.examples.push((state_num, problem));
+        }
+    }
+}
+
+/// Every `BLOCK`th key, gathered while checking the key order, so finding a record
+/// reads one block from disk rather than searching the whole file there.
+struct Index {
+    keys: Vec<u64>,
+    len: u64,
+}
+
+impl Index {
+    fn winner_of(&self, file: &mut File, state_num: u64) -> io::Result<Option<i8>> {
+        let block = match self.keys.partition_point(|&key| key <= state_num) {
+            0 => return Ok(None),
+            block => block as u64 - 1,
+        };
+        let start = block * BLOCK;
+        let count = BLOCK.min(self.len - start) as usize;
+        let mut buffer = [0u8; (BLOCK * RECORD_SIZE) as usize];
+        let buffer = &mut buffer[..count * RECORD_SIZE as usize];
+        file.seek(SeekFrom::Start(start * RECORD_SIZE))?;
+        file.read_exact(buffer)?;
+        let position = buffer
+            .chunks_exact(RECORD_SIZE as usize)
+            .position(|record| u64::from_le_bytes(record[0..8].try_into().unwrap()) == state_num);
+        Ok(position.map(|index| buffer[index * RECORD_SIZE as usize + 9] as i8))
+    }
+}
+
+fn has_line(g: &Game, player: i8) -> bool {
+    let mut only_player = g.clone();
+    for column in only_player.board.data.iter_mut() {
+        for peg in column.iter_mut() {
+            for cell in peg.iter_mut() {
+                if *cell != player {
+                    *cell = 0;
+                }
+            }
+        }
+    }
+    is_over(&only_player)
+}
+
+fn is_legal_encoding(state_num: u64, g: &Game) -> bool {
+    if game_to_number(g.clone()) != state_num {
+        return false;
+    }
+    let mut ones = 0;
+    let mut twos = 0;
+    for x in 0..3 {
+        for y in 0..3 {
+            let mut landed = true;
+            for z in 0..3 {
+                match g.board.data[x][y][z] {
+                    0 => landed = false,
+                    value => {
+                        if !landed {
+                            return false;
+                        }
+                        if value == 1 {
+                            ones += 1;
+                        } else {
+                            twos += 1;
+                        }
+                    }
+                }
+            }
+        }
+    }
+    ones == twos || ones == twos + 1
+}
+
+fn check_record(index: &Index, file: &mut File, record: &Record) -> io::Result<Option<Problem>> {
+    let state_num = record.state;
+    let stored_move = record.stored_move;
+    let winner = record.winner;
+    if !(0..=2).contains(&winner) {
+        return Ok(Some(Problem::BadWinner));
+    }
+    let g = number_to_board(state_num);
+    if !is_legal_encoding(state_num, &g) {
+        return Ok(Some(Problem::InvalidState));
+    }
+    let last_player = switch_player(g.player);
+    if has_line(&g, g.player) {
+        return Ok(Some(Problem::Unreachable));
+    }
+    if is_over(&g) || is_full(&g) {
+        let expected = if is_over(&g) { last_player } else { 0 };
+        if winner != expected {
+            return Ok(Some(Problem::TerminalValue));
+        }
+        if stored_move != -1 {
+            return Ok(Some(Problem::TerminalMove));
+        }
+        return Ok(None);
+    }
+
+    let player = g.player;
+    let next_numbers = get_all_next_numbers(g);
+    let mut has_win = false;
+    let mut has_tie = false;
+    for &num in &next_numbers {
+        match index.winner_of(file, num)? {
+            None => return Ok(Some(Problem::MissingChild)),
+            Some(value) if value == player => has_win = true,
+            Some(0) => has_tie = true,
+            Some(_) => {}
+        }
+    }
+    let expected = if has_win {
+        player
+    } else if has_tie {
+        0
+    } else {
+        last_player
+    };
+    if winner != expected {
+        return Ok(Some(Problem::WrongValue));
+    }
+    match stored_move_to_next_number(state_num, player, stored_move) {
+        Some(next) if next_numbers.contains(&next) => {
+            if index.winner_of(file, next)? != Some(winner) {
+                return Ok(Some(Problem::MoveValue));
+            }
+        }
+        _ => return Ok(Some(Problem::BadMove)),
+    }
+    Ok(None)
+}
+
+/// Checks every record of `path` and that `root` is among them, printing progress
+/// as it goes. Errors are files that cannot be read to the end.
+fn check(path: &str, root: u64) -> Result<Report, String> {
+    let database =
+        Database::open(path).map_err(|error| format!("Could not open {}: {}", path, error))?;
+    let total = database.len();
+    println!("Reading {} records from {}", total, path);
+
+    let mut report = Report::default();
+    let mut index = Index {
+        keys: Vec::with_capacity((total / BLOCK + 1) as usize),
+        len: total,
+    };
+    let mut previous: Option<u64> = None;
+    let mut read: u64 = 0;
+    for record in database.records().unwrap() {
+        let record =
+            record.map_err(|error| format!("Read failed after {} records: {}", read, error))?;
+        if previous.is_some_and(|previous| record.state <= previous) {
+            report.add(record.state, Problem::Unsorted);
+        }
+        previous = Some(record.state);
+        if read.is_multiple_of(BLOCK) {
+            index.keys.push(record.state);
+        }
+        read += 1;
+        if read.is_multiple_of(10000000) {
+            println!("{} / {}", read, total);
+        }
+    }
+    println!("Reading Done");
+
+    if !report.counts.is_empty() {
+        println!("Keys are out of order, skipping the minimax checks");
+        return Ok(report);
+    }
+    println!("Checking Records");
+    let mut records = database.records().unwrap();
+    let mut checked: u64 = 0;
+    loop {
+        let chunk: Vec<Record> = records
+            .by_ref()
+            .take(CHUNK_SIZE)
+            .collect::<io::Result<Vec<Record>>>()
+            .map_err(|error| format!("Read failed after {} records: {}", checked, error))?;
+        if chunk.is_empty() {
+            break;
+        }
+        // Each worker opens its own handle so lookups do not queue on one file
+        let found: Vec<io::Result<Option<Problem>>> = chunk
+            .par_iter()
+            .map_init(
+                || File::open(path),
+                |file, record| match file {
+                    Ok(file) => check_record(&index, file, record),
+                    Err(error) => Err(io::Error::new(error.kind(), error.to_string())),
+                },
+            )
+            .collect();
+        for (record, problem) in chunk.iter().zip(found) {
+            match problem {
+                Ok(Some(problem)) => report.add(record.state, problem),
+                Ok(None) => {}
+                Err(error) => {
+                    return Err(format!(
+                        "Lookup failed at state {}: {}",
+                        record.state, error
+                    ))
+                }
+            }
+        }
+        let done = checked + chunk.len() as u64;
+        if checked / 10000000 != done / 10000000 {
+            println!("{} / {}", done, total);
+        }
+        checked = done;
+    }
+    println!("Checking Done");
+
+    println!("Records: {}", total);
+    match database.lookup(root) {
+        Ok(Some(record)) => println!(
+            "Starting position (state {}) winner: {}",
+            root, record.winner
+        ),
+        Ok(None) => report.add(root, Problem::MissingRoot),
+        Err(error) => return Err(format!("Lookup failed at state {}: {}", root, error)),
+    }
+    Ok(report)
+}
+
+/// Checks that `path` is a complete, consistent solution. Keys must be sorted and
+/// unique, the empty board must be present, finished games must hold the outcome
+/// found by `is_over`, and every other record must hold the minimax of its
+/// children along with a move that reaches a child of that value. The file is
+/// streamed twice, first for the key order and then in chunks whose records are
+/// checked in parallel, with the children found through a sparse index of keys.
+pub fn verify(path: &str) -> bool {
+    let report = match check(path, 0) {
+        Ok(report) => report,
+        Err(error) => {
+            println!("{}", error);
+            return false;
+        }
+    };
+    if report.counts.is_empty() {
+        println!("No problems found");
+        return true;
+    }
+    for (problem, count) in &report.counts {
+        println!("{}: {}", problem.describe(), count);
+    }
+    println!("First bad entries:");
+    for (state_num, problem) in &report.examples {
+        println!("  {}: {}", state_num, problem.describe());
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notation;
+    use crate::test_support::{temp_path, write_database};
+    use crate::variant::Variant;
+
+    const ROOT: &str = "5 8 8 3 6 2 3 4 7 7 9 9";
+
+    /// The records of a database solved from `ROOT`, and the root's state.
+    fn records() -> (Vec<[u8; 10]>, u64) {
+        let variant = Variant::new(3, 3).unwrap();
+        let pegs = notation::parse_moves(ROOT, 3).unwrap();
+        let root = notation::play_moves(&variant, &pegs).unwrap();
+        let path = temp_path("verify-source.bin");
+        write_database(&variant, root, &path);
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+        let records = bytes
+            .chunks_exact(RECORD_SIZE as usize)
+            .map(|record| record.try_into().unwrap())
+            .collect();
+        (records, root.state_number())
+    }
+
+    fn problems(records: &[[u8; 10]], root: u64) -> BTreeMap<Problem, u64> {
+        let path = temp_path("verify.bin");
+        std::fs::write(&path, records.concat()).unwrap();
+        let report = check(&path, root).unwrap();
+        std::fs::remove_file(path).unwrap();
+        report.counts
+    }
+
+    /// The first record of an unfinished position, which has a move stored.
+    fn unfinished(records: &[[u8; 10]]) -> usize {
+        records
+            .iter()
+            .position(|record| record[8] as i8 >= 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn a_solved_file_passes() {
+        let (records, root) = records();
+        assert!(problems(&records, root).is_empty());
+    }
+
+    #[test]
+    fn each_fault_is_reported() {
+        let (records, root) = records();
+
+        let mut swapped = records.clone();
+        swapped.swap(0, 1);
+        assert!(problems(&swapped, root).contains_key(&Problem::Unsorted));
+
+        let mut wrong_winner = records.clone();
+        let index = unfinished(&records);
+        wrong_winner[index][9] = (wrong_winner[index][9] + 1) % 3;
+        assert!(problems(&wrong_winner, root).contains_key(&Problem::WrongValue));
+
+        // The first move's cell on peg 5 is taken in every position here
+        let mut bad_move = records.clone();
+        bad_move[index][8] = 4;
+        assert!(problems(&bad_move, root).contains_key(&Problem::BadMove));
+
+        let without_root: Vec<[u8; 10]> = records
+            .iter()
+            .filter(|record| u64::from_le_bytes(record[0..8].try_into().unwrap()) != root)
+            .copied()
+            .collect();
+        assert_eq!(without_root.len(), records.len() - 1);
+        assert!(problems(&without_root, root).contains_key(&Problem::MissingRoot));
+    }
+
+    #[test]
+    fn an_empty_file_fails() {
+        assert!(problems(&[], 0).contains_key(&Problem::MissingRoot));
+    }
+}
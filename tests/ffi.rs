@@ -0,0 +1,93 @@
+//! Checks `include/connect3.h` against cbindgen, then builds `examples/ffi.c`
+//! against it and the cdylib and runs it.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const ROOT: &str = env!("CARGO_MANIFEST_DIR");
+
+/// Cargo builds the cdylib in `target/<profile>/deps`, beside the test, and
+/// copies it up to `target/<profile>`. Either will do.
+fn library_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let deps = exe.parent().unwrap();
+    let name = format!(
+        "{}connect3{}",
+        std::env::consts::DLL_PREFIX,
+        std::env::consts::DLL_SUFFIX
+    );
+    let found = [deps, deps.parent().unwrap()]
+        .into_iter()
+        .find(|dir| dir.join(&name).is_file())
+        .unwrap_or(deps);
+    found.to_path_buf()
+}
+
+/// Set to rewrite `include/connect3.h` instead of comparing it.
+const WRITE_HEADER: &str = "CONNECT3_WRITE_HEADER";
+
+#[test]
+fn header_matches_cbindgen() {
+    let config = cbindgen::Config::from_file(Path::new(ROOT).join("cbindgen.toml")).unwrap();
+    let mut generated = Vec::new();
+    cbindgen::generate_with_config(ROOT, config)
+        .unwrap()
+        .write(&mut generated);
+    let path = Path::new(ROOT).join("include/connect3.h");
+    if std::env::var_os(WRITE_HEADER).is_some() {
+        fs::write(&path, &generated).unwrap();
+        return;
+    }
+    assert!(
+        fs::read(&path).unwrap() == generated,
+        "include/connect3.h is out of date with src/ffi.rs, rerun with {}=1",
+        WRITE_HEADER
+    );
+}
+
+#[test]
+fn c_example() {
+    let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR"));
+    let example = out_dir.join("ffi_example");
+    let library = library_dir();
+    let compiler = std::env::var("CC").unwrap_or("cc".to_string());
+    let status = Command::new(&compiler)
+        .arg(Path::new(ROOT).join("examples/ffi.c"))
+        .arg("-I")
+        .arg(Path::new(ROOT).join("include"))
+        .arg("-L")
+        .arg(&library)
+        .arg("-lconnect3")
+        .arg("-o")
+        .arg(&example)
+        .status();
+    match status {
+        Ok(status) => assert!(status.success(), "{} failed on examples/ffi.c", compiler),
+        Err(error) => panic!(
+            "{} did not run: {}, set CC to a C compiler to build examples/ffi.c",
+            compiler, error
+        ),
+    }
+
+    // A one record database: the empty board, stored move 4 (peg 5), player 1 wins
+    let database = out_dir.join("ffi_example.bin");
+    let mut record = 0_u64.to_le_bytes().to_vec();
+    record.extend([4, 1]);
+    fs::write(&database, record).unwrap();
+
+    let output = Command::new(&example)
+        .arg(&database)
+        .env("LD_LIBRARY_PATH", &library)
+        .env("DYLD_LIBRARY_PATH", &library)
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "{}{}",
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stdout.contains("best 5 winner 1"), "{}", stdout);
+}